@@ -1,6 +1,6 @@
 #![deny(warnings, clippy::all)]
 
-use cdr::{BigEndian, CdrBe, Infinite};
+use cdr::{BigEndian, CdrBe, Infinite, Primitives};
 use criterion::{criterion_group, criterion_main, Criterion};
 use serde_derive::{Deserialize, Serialize};
 
@@ -35,11 +35,7 @@ fn compose_lidar_points_msg() -> LidarPointsMsg {
     };
     let points = vec![
         LidarPoint {
-            position: [
-                std::f32::EPSILON,
-                std::f32::EPSILON * 1.,
-                std::f32::EPSILON * 2.,
-            ],
+            position: [f32::EPSILON, f32::EPSILON * 1., f32::EPSILON * 2.,],
             intensity: 7,
         };
         10_000
@@ -47,6 +43,31 @@ fn compose_lidar_points_msg() -> LidarPointsMsg {
     LidarPointsMsg { msg_info, points }
 }
 
+#[derive(Serialize, Deserialize)]
+struct LidarCloudMsg {
+    msg_info: MsgInfo,
+    positions: Vec<f32>,
+    intensities: Vec<u8>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct LidarBulkCloudMsg {
+    msg_info: MsgInfo,
+    positions: Primitives<f32>,
+    intensities: Primitives<u8>,
+}
+
+fn compose_lidar_cloud_msg() -> LidarCloudMsg {
+    let LidarPointsMsg { msg_info, points } = compose_lidar_points_msg();
+    let positions = points.iter().flat_map(|p| p.position).collect();
+    let intensities = points.iter().map(|p| p.intensity).collect();
+    LidarCloudMsg {
+        msg_info,
+        positions,
+        intensities,
+    }
+}
+
 fn lidar_point_msg(b: &mut Criterion) {
     let msg = compose_lidar_points_msg();
     b.bench_function("lidar point msg", |b| {
@@ -78,6 +99,35 @@ fn lidar_point_msg_bincode(b: &mut Criterion) {
     });
 }
 
+fn lidar_cloud_msg(b: &mut Criterion) {
+    let msg = compose_lidar_cloud_msg();
+    b.bench_function("lidar cloud msg", |b| {
+        b.iter(|| {
+            let encoded = cdr::serialize::<_, _, CdrBe>(&msg, Infinite).unwrap();
+            let _decoded = cdr::deserialize::<LidarCloudMsg>(&encoded).unwrap();
+        });
+    });
+}
+
+fn lidar_cloud_msg_primitives(b: &mut Criterion) {
+    let LidarCloudMsg {
+        msg_info,
+        positions,
+        intensities,
+    } = compose_lidar_cloud_msg();
+    let msg = LidarBulkCloudMsg {
+        msg_info,
+        positions: Primitives(positions),
+        intensities: Primitives(intensities),
+    };
+    b.bench_function("lidar cloud msg primitives", |b| {
+        b.iter(|| {
+            let encoded = cdr::serialize::<_, _, CdrBe>(&msg, Infinite).unwrap();
+            let _decoded = cdr::deserialize::<LidarBulkCloudMsg>(&encoded).unwrap();
+        });
+    });
+}
+
 const MSG: &str = r#"What's he that wishes so?
     My cousin Westmoreland? No, my fair cousin:
     If we are mark'd to die, we are enow
@@ -162,6 +212,8 @@ criterion_group!(
     lidar_point_msg,
     lidar_point_msg_without_encapsulation,
     lidar_point_msg_bincode,
+    lidar_cloud_msg,
+    lidar_cloud_msg_primitives,
     string_msg,
     string_msg_without_encapsulation,
     string_msg_bincode
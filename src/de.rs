@@ -1,86 +1,693 @@
 //! Deserializing CDR into Rust data types.
 
-use std::{self, io::Read, marker::PhantomData};
+use alloc::{borrow::Cow, string::String, vec::Vec};
+use core::marker::PhantomData;
 
-use byteorder::{BigEndian, ByteOrder, LittleEndian, ReadBytesExt};
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
 use serde::de::{self, IntoDeserializer};
 
+mod read;
+pub(crate) use self::read::unexpected_eof;
+use self::read::DynReader;
+pub use self::read::{CdrRead, SliceReader};
+
 use crate::{
-    error::{Error, Result},
-    size::{Infinite, SizeLimit},
+    encapsulated::{ENCAPSULATED_NAME, FLAG_BE, FLAG_LE},
+    encapsulation::{CdrVersion, EncapsulationKind, Endianness, Extensibility},
+    error::{Error, PathSegment, Result},
+    parameter::{
+        field_name, find_member, MemberIds, Optional, EMHEADER_ID_MASK, EMHEADER_LC_SHIFT,
+        EMHEADER_MUST_UNDERSTAND, LC_NEXTINT, PID_EXTENDED, PID_EXTENDED_ID_MASK,
+        PID_EXTENDED_MUST_UNDERSTAND, PID_ID_MASK, PID_MUST_UNDERSTAND, PID_SENTINEL,
+    },
+    primitive::{is_native, swap_bytes, Bulk},
+    size::{Bounded, Infinite, SizeLimit, DEFAULT_MAX_DEPTH},
+    wide::{
+        decode_utf16, decode_utf16_with_bom, decode_wchar, WideEncoding, WCHAR_NAME, WSTRING_NAME,
+    },
+    xtypes::{extensibility_of, DELIMITED_NAME},
 };
 
+/// Default for the largest number of bytes or elements reserved up front for
+/// data whose length is read from the input.
+pub const DEFAULT_MAX_PREALLOCATION: usize = 64 * 1024;
+
+/// How strictly padding is checked while reading.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum PaddingMode {
+    /// Padding is skipped whatever its content, and padding declared in the
+    /// encapsulation options may be missing or inconsistent with the data,
+    /// as some peers write it.
+    Lenient,
+    /// Padding is skipped whatever its content, while padding declared in
+    /// the encapsulation options must be present and end the data on a
+    /// multiple of four bytes.
+    Normal,
+    /// Like `Normal`, and every padding byte must be zero.
+    Strict,
+}
+
+impl Default for PaddingMode {
+    fn default() -> Self {
+        PaddingMode::Normal
+    }
+}
+
 /// A deserializer that reads bytes from a buffer.
 pub struct Deserializer<R, S, E> {
     reader: R,
     size_limit: S,
+    /// Offset in the input at which the position was `start`.
+    base: u64,
+    start: u64,
     pos: u64,
+    version: CdrVersion,
+    extensibility: Extensibility,
+    /// Whether a struct or an enum is being read, whose nested structs are
+    /// final unless annotated otherwise.
+    in_aggregate: bool,
+    peeked: Vec<u8>,
+    optional: Optional,
+    /// Whether the next sequence, array or map is preceded by a DHEADER, as
+    /// `Delimited` asks.
+    delimit: bool,
+    /// Extensibility kind of the next struct, as `Final`, `Appendable` or
+    /// `Mutable` gives it.
+    annotated: Option<Extensibility>,
+    /// Size of the elements of the next array if a `PrimitiveArray` reads it
+    /// in bulk.
+    bulk_array: Option<usize>,
+    ascii_only: bool,
+    wide_encoding: WideEncoding,
+    max_preallocation: usize,
+    padding_mode: PaddingMode,
+    error_positions: bool,
+    depth: usize,
+    max_depth: usize,
     phantom: PhantomData<E>,
 }
 
 impl<R, S, E> Deserializer<R, S, E>
 where
-    R: Read,
     S: SizeLimit,
     E: ByteOrder,
 {
     pub fn new(reader: R, size_limit: S) -> Self {
+        Self::new_at(reader, size_limit, 0)
+    }
+
+    /// Creates a deserializer for input that starts `pos` bytes after the
+    /// origin that alignment is relative to, as when it was cut from a larger
+    /// CDR stream at that offset. Offsets in errors are still counted from the
+    /// start of the input.
+    pub fn new_at(reader: R, size_limit: S, pos: u64) -> Self {
         Self {
             reader,
             size_limit,
-            pos: 0,
+            base: 0,
+            start: pos,
+            pos,
+            version: CdrVersion::default(),
+            extensibility: Extensibility::default(),
+            in_aggregate: false,
+            peeked: Vec::new(),
+            optional: Optional::Value,
+            delimit: false,
+            annotated: None,
+            bulk_array: None,
+            ascii_only: false,
+            wide_encoding: WideEncoding::default(),
+            max_preallocation: DEFAULT_MAX_PREALLOCATION,
+            padding_mode: PaddingMode::default(),
+            error_positions: false,
+            depth: 0,
+            max_depth: DEFAULT_MAX_DEPTH,
             phantom: PhantomData,
         }
     }
 
+    /// Sets the version of the CDR representation to read.
+    pub fn with_version(mut self, version: CdrVersion) -> Self {
+        self.version = version;
+        self
+    }
+
+    /// Sets the extensibility kind expected for the outermost structs.
+    pub fn with_extensibility(mut self, extensibility: Extensibility) -> Self {
+        self.extensibility = extensibility;
+        self
+    }
+
+    /// Rejects strings containing non-ASCII characters, which are otherwise
+    /// read as UTF-8.
+    pub fn with_ascii_only(mut self, ascii_only: bool) -> Self {
+        self.ascii_only = ascii_only;
+        self
+    }
+
+    /// Sets the conventions for reading `WChar` and `WString`.
+    pub fn with_wide_encoding(mut self, wide_encoding: WideEncoding) -> Self {
+        self.wide_encoding = wide_encoding;
+        self
+    }
+
+    /// Sets the largest number of bytes or elements reserved up front for
+    /// strings, sequences and maps, whose lengths come from the input and
+    /// cannot be trusted. Longer data is still read, with the buffer growing
+    /// as bytes actually arrive.
+    pub fn with_max_preallocation(mut self, max_preallocation: usize) -> Self {
+        self.max_preallocation = max_preallocation;
+        self
+    }
+
+    /// Sets how strictly padding is checked.
+    pub fn with_padding_mode(mut self, padding_mode: PaddingMode) -> Self {
+        self.padding_mode = padding_mode;
+        self
+    }
+
+    /// Sets the deepest nesting of compound values, options and newtypes
+    /// allowed before failing with `Error::RecursionLimitExceeded`.
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Wraps decode errors in `Error::Positioned`, which holds the offset of
+    /// the value that failed and the path of fields and indices leading to
+    /// it, instead of returning them as they are.
+    pub fn with_error_positions(mut self, error_positions: bool) -> Self {
+        self.error_positions = error_positions;
+        self
+    }
+}
+
+impl<'de, S, E> Deserializer<SliceReader<'de>, S, E>
+where
+    S: SizeLimit,
+    E: ByteOrder,
+{
+    /// Creates a deserializer reading from a slice, which lets `&'de str` and
+    /// `&'de [u8]` borrow from it.
+    pub fn from_slice(slice: &'de [u8], size_limit: S) -> Self {
+        Self::new(SliceReader::new(slice), size_limit)
+    }
+
+    /// Creates a deserializer reading from a slice that starts `pos` bytes
+    /// after the origin that alignment is relative to.
+    pub fn from_slice_at(slice: &'de [u8], size_limit: S, pos: u64) -> Self {
+        Self::new_at(SliceReader::new(slice), size_limit, pos)
+    }
+}
+
+impl<'de, R, S, E> Deserializer<R, S, E>
+where
+    R: CdrRead<'de>,
+    S: SizeLimit,
+    E: ByteOrder,
+{
     fn read_padding_of<T>(&mut self) -> Result<()> {
+        self.read_padding_for(core::mem::size_of::<T>())
+    }
+
+    fn read_padding_for(&mut self, size: usize) -> Result<()> {
         // Calculate the required padding to align with 1-byte, 2-byte, 4-byte, 8-byte
         // boundaries Instead of using the slow modulo operation '%', the faster
         // bit-masking is used
-        let alignment = std::mem::size_of::<T>();
+        let alignment = size.min(self.version.max_alignment());
         let rem_mask = alignment - 1; // mask like 0x0, 0x1, 0x3, 0x7
-        let mut padding: [u8; 8] = [0; 8];
         match (self.pos as usize) & rem_mask {
             0 => Ok(()),
-            n @ 1..=7 => {
-                let amt = alignment - n;
-                self.read_size(amt as u64)?;
-                self.reader
-                    .read_exact(&mut padding[..amt])
-                    .map_err(Into::into)
-            }
+            n @ 1..=7 => self.read_padding(alignment - n),
             _ => unreachable!(),
         }
     }
 
+    /// Reads `amt` bytes of padding, which must be zero in strict mode.
+    fn read_padding(&mut self, amt: usize) -> Result<()> {
+        let mut padding: [u8; 8] = [0; 8];
+        let start = self.pos;
+        self.read_size(amt as u64)?;
+        self.read_exact(&mut padding[..amt])?;
+        if self.padding_mode == PaddingMode::Strict {
+            if let Some(i) = padding[..amt].iter().position(|&b| b != 0) {
+                return Err(Error::InvalidPadding(self.offset_of(start) + i as u64));
+            }
+        }
+        Ok(())
+    }
+
+    /// Fills `buf` from the bytes peeked ahead first and then from the reader.
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<()> {
+        self.optional = Optional::Value;
+        if self.delimit || self.annotated.is_some() {
+            // Only collections can be delimited, and only structs annotated.
+            return Err(Error::TypeNotSupported);
+        }
+        if self.peeked.is_empty() {
+            return self.reader.read_into(buf);
+        }
+
+        let n = self.peeked.len().min(buf.len());
+        buf[..n].copy_from_slice(&self.peeked[..n]);
+        self.peeked.drain(..n);
+        self.reader.read_into(&mut buf[n..])
+    }
+
+    /// Begins a sequence, an array or a map, returning the end of its
+    /// elements if `Delimited` asks for a DHEADER.
+    fn begin_collection(&mut self) -> Result<Option<u64>> {
+        if self.annotated.is_some() {
+            return Err(Error::TypeNotSupported);
+        }
+        if !core::mem::take(&mut self.delimit) {
+            return Ok(None);
+        }
+        let size: u32 = de::Deserialize::deserialize(&mut *self)?;
+        Ok(Some(self.pos + u64::from(size)))
+    }
+
+    /// Fails if a `Delimited` wraps something other than a collection, or a
+    /// `Final`, an `Appendable` or a `Mutable` something other than a struct.
+    fn check_unwrapped(&mut self) -> Result<()> {
+        let delimit = core::mem::take(&mut self.delimit);
+        if self.annotated.take().is_some() || delimit {
+            Err(Error::TypeNotSupported)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Reads the elements of a collection, skipping to `end` afterwards if a
+    /// DHEADER gave it.
+    fn read_elements<V>(&mut self, len: usize, end: Option<u64>, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        let value = self.nested(|this| {
+            visitor.visit_seq(ElementAccess {
+                deserializer: this,
+                len,
+                index: 0,
+                fields: None,
+                member_ids: MemberIds::default(),
+            })
+        })?;
+        if let Some(end) = end {
+            self.skip_to(end)?;
+        }
+        Ok(value)
+    }
+
+    /// Reads the next `u32` without consuming it.
+    fn peek_u32(&mut self) -> Result<u32> {
+        let mut buf = [0; 4];
+        self.read_exact(&mut buf)?;
+        self.peeked.splice(..0, buf.iter().copied());
+        Ok(E::read_u32(&buf))
+    }
+
     fn read_size(&mut self, size: u64) -> Result<()> {
         self.pos += size;
         self.size_limit.add(size)
     }
 
     fn read_size_of<T>(&mut self) -> Result<()> {
-        self.read_size(std::mem::size_of::<T>() as u64)
+        self.read_size(core::mem::size_of::<T>() as u64)
     }
 
-    fn read_string(&mut self) -> Result<String> {
-        String::from_utf8(self.read_bytes().map(|mut v| {
-            v.pop(); // removes a terminating null character
-            v
-        })?)
-        .map_err(|e| Error::InvalidUtf8Encoding(e.utf8_error()))
+    fn read_string(&mut self) -> Result<Cow<'de, str>> {
+        // Removes a terminating null character.
+        let s = match self.read_bytes()? {
+            Cow::Borrowed(v) => {
+                let v = v.split_last().map_or(v, |(_, v)| v);
+                Cow::Borrowed(core::str::from_utf8(v).map_err(Error::InvalidUtf8Encoding)?)
+            }
+            Cow::Owned(mut v) => {
+                v.pop();
+                Cow::Owned(
+                    String::from_utf8(v).map_err(|e| Error::InvalidUtf8Encoding(e.utf8_error()))?,
+                )
+            }
+        };
+        if self.ascii_only && !s.is_ascii() {
+            Err(Error::InvalidString(s.into_owned()))
+        } else {
+            Ok(s)
+        }
     }
 
-    fn read_bytes(&mut self) -> Result<Vec<u8>> {
+    /// Reads a length-prefixed sequence of bytes, borrowing it from the input
+    /// if the reader allows.
+    fn read_bytes(&mut self) -> Result<Cow<'de, [u8]>> {
         let len: u32 = de::Deserialize::deserialize(&mut *self)?;
-        let mut buf = vec![0_u8; len as usize];
-        self.read_size(u64::from(len))?;
-        self.reader.read_exact(&mut buf[..])?;
+        if self.peeked.is_empty() {
+            if let Some(bytes) = self.reader.read_borrowed(len as usize)? {
+                self.read_size(u64::from(len))?;
+                return Ok(Cow::Borrowed(bytes));
+            }
+        }
+        self.read_vec(u64::from(len)).map(Cow::Owned)
+    }
+
+    /// Reads `len` primitive values of `size` bytes each with a single
+    /// alignment, handing them to `visitor` in the byte order of the host.
+    fn read_bulk<V>(&mut self, size: usize, len: usize, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        if len == 0 {
+            return visitor.visit_bytes(&[]);
+        }
+
+        let len = len.checked_mul(size).ok_or(Error::NumberOutOfRange)?;
+        self.read_padding_for(size)?;
+        if !is_native::<E>() {
+            let mut buf = self.read_vec(len as u64)?;
+            swap_bytes(&mut buf, size);
+            return visitor.visit_byte_buf(buf);
+        }
+
+        if self.peeked.is_empty() {
+            if let Some(bytes) = self.reader.read_borrowed(len)? {
+                self.read_size(len as u64)?;
+                return visitor.visit_borrowed_bytes(bytes);
+            }
+        }
+        visitor.visit_byte_buf(self.read_vec(len as u64)?)
+    }
+
+    fn read_vec(&mut self, len: u64) -> Result<Vec<u8>> {
+        let len = usize::try_from(len).map_err(|_| Error::NumberOutOfRange)?;
+        let capacity = self.cautious_len(len);
+        if let Some(remaining) = self.reader.remaining_len() {
+            if len > remaining + self.peeked.len() {
+                return Err(unexpected_eof());
+            }
+        }
+        self.read_size(len as u64)?;
+
+        // Grows the buffer no faster than the input fills it.
+        let mut buf = Vec::with_capacity(capacity);
+        while buf.len() < len {
+            let start = buf.len();
+            let step = start.max(self.max_preallocation).max(1);
+            buf.resize(len.min(start + step), 0);
+            self.read_exact(&mut buf[start..])?;
+        }
         Ok(buf)
     }
 
+    /// Returns how many of `len` bytes or elements can be reserved up front,
+    /// which is bounded by the input left, the size limit and the maximum
+    /// preallocation.
+    fn cautious_len(&self, len: usize) -> usize {
+        let mut len = len.min(self.max_preallocation);
+        if let Some(remaining) = self.reader.remaining_len() {
+            len = len.min(remaining + self.peeked.len());
+        }
+        if let Some(limit) = self.size_limit.limit() {
+            len = len.min(usize::try_from(limit).unwrap_or(usize::MAX));
+        }
+        len
+    }
+
+    /// Reads a value in an encapsulation, an octet sequence starting with a
+    /// byte-order flag, with alignment relative to the start of the sequence.
+    fn read_encapsulated<V>(&mut self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        let len: u32 = de::Deserialize::deserialize(&mut *self)?;
+        let end = self.pos + u64::from(len);
+        if len == 0 {
+            return Err(Error::InvalidEncapsulation);
+        }
+
+        let mut flag = [0];
+        self.read_exact(&mut flag)?;
+        self.read_size(1)?;
+        let value = match flag[0] {
+            FLAG_BE => self.read_encapsulated_as::<BigEndian, _>(visitor)?,
+            FLAG_LE => self.read_encapsulated_as::<LittleEndian, _>(visitor)?,
+            _ => return Err(Error::InvalidEncapsulation),
+        };
+        self.skip_to(end)?;
+        Ok(value)
+    }
+
+    /// Reads the value of an encapsulation in the byte order `F` with another
+    /// deserializer over the same reader, whose type does not depend on ours
+    /// so that encapsulations can nest.
+    fn read_encapsulated_as<F, V>(&mut self, visitor: V) -> Result<V::Value>
+    where
+        F: ByteOrder,
+        V: de::Visitor<'de>,
+    {
+        let base = self.offset_of(self.pos);
+        let mut deserializer = Deserializer::<_, _, F> {
+            reader: DynReader(&mut self.reader),
+            size_limit: Bounded(self.size_limit.limit().unwrap_or(u64::MAX)),
+            base,
+            start: 1,
+            pos: 1,
+            version: self.version,
+            extensibility: self.extensibility,
+            in_aggregate: self.in_aggregate,
+            peeked: core::mem::take(&mut self.peeked),
+            optional: Optional::Value,
+            delimit: false,
+            annotated: None,
+            bulk_array: None,
+            ascii_only: self.ascii_only,
+            wide_encoding: self.wide_encoding,
+            max_preallocation: self.max_preallocation,
+            padding_mode: self.padding_mode,
+            error_positions: self.error_positions,
+            depth: self.depth,
+            max_depth: self.max_depth,
+            phantom: PhantomData,
+        };
+        let result = deserializer.nested(|this| visitor.visit_newtype_struct(this));
+        let read = deserializer.pos - 1;
+        self.peeked = deserializer.peeked;
+        let value = result?;
+        self.read_size(read)?;
+        Ok(value)
+    }
+
+    fn read_wchar(&mut self) -> Result<char> {
+        match self.wide_encoding {
+            WideEncoding::Xcdr => {
+                let unit: u16 = de::Deserialize::deserialize(&mut *self)?;
+                char::from_u32(u32::from(unit)).ok_or(Error::InvalidCharEncoding)
+            }
+            WideEncoding::Giop => {
+                let len: u8 = de::Deserialize::deserialize(&mut *self)?;
+                let buf = self.read_vec(u64::from(len))?;
+                decode_wchar(&decode_utf16_with_bom(&buf)?)
+            }
+        }
+    }
+
+    fn read_wstring(&mut self) -> Result<String> {
+        let len: u32 = de::Deserialize::deserialize(&mut *self)?;
+        match (self.wide_encoding, self.version) {
+            (WideEncoding::Xcdr, CdrVersion::Xcdr1) => {
+                // The length counts code units, including a terminating null.
+                let buf = self.read_vec(u64::from(len) * 2)?;
+                let mut s = decode_utf16::<E>(&buf)?;
+                if s.ends_with('\0') {
+                    s.pop();
+                }
+                Ok(s)
+            }
+            (WideEncoding::Xcdr, CdrVersion::Xcdr2) => {
+                let buf = self.read_vec(u64::from(len))?;
+                decode_utf16::<E>(&buf)
+            }
+            (WideEncoding::Giop, _) => {
+                let buf = self.read_vec(u64::from(len))?;
+                decode_utf16_with_bom(&buf)
+            }
+        }
+    }
+
+    fn skip_to(&mut self, end: u64) -> Result<()> {
+        if self.pos > end {
+            return Err(Error::LengthExceeded);
+        }
+
+        let amt = end - self.pos;
+        self.read_size(amt)?;
+        let peeked = self.peeked.len().min(amt as usize);
+        self.peeked.drain(..peeked);
+        self.reader.skip(amt - peeked as u64)
+    }
+
+    /// Runs `f` within a struct or an enum, one level of nesting deeper.
+    fn aggregate<T, F>(&mut self, f: F) -> Result<T>
+    where
+        F: FnOnce(&mut Self) -> Result<T>,
+    {
+        let in_aggregate = core::mem::replace(&mut self.in_aggregate, true);
+        let result = self.nested(f);
+        self.in_aggregate = in_aggregate;
+        result
+    }
+
+    /// Reads a parameter header, returning the member id, the length and the
+    /// must-understand flag of the parameter, or `None` at the end of the
+    /// list.
+    fn read_parameter_header(&mut self) -> Result<Option<(u32, u64, bool)>> {
+        self.read_padding_of::<u32>()?;
+        let pid: u16 = de::Deserialize::deserialize(&mut *self)?;
+        let len: u16 = de::Deserialize::deserialize(&mut *self)?;
+        match pid & PID_ID_MASK {
+            PID_SENTINEL => Ok(None),
+            PID_EXTENDED => {
+                let end = self.pos + u64::from(len);
+                let id: u32 = de::Deserialize::deserialize(&mut *self)?;
+                let len: u32 = de::Deserialize::deserialize(&mut *self)?;
+                self.skip_to(end)?;
+                let must_understand = id & PID_EXTENDED_MUST_UNDERSTAND != 0;
+                Ok(Some((
+                    id & PID_EXTENDED_ID_MASK,
+                    u64::from(len),
+                    must_understand,
+                )))
+            }
+            id => {
+                let must_understand = pid & PID_MUST_UNDERSTAND != 0;
+                Ok(Some((u32::from(id), u64::from(len), must_understand)))
+            }
+        }
+    }
+
+    /// Reads an EMHEADER and the NEXTINT following it, returning the member
+    /// id, the length and the must-understand flag of the member, or `None`
+    /// once `end` is reached.
+    fn read_emheader(&mut self, end: u64) -> Result<Option<(u32, u64, bool)>> {
+        if self.pos >= end {
+            return Ok(None);
+        }
+
+        let header: u32 = de::Deserialize::deserialize(&mut *self)?;
+        let len = match (header >> EMHEADER_LC_SHIFT) & 0x7 {
+            lc @ 0..=3 => 1 << lc,
+            LC_NEXTINT => {
+                let len: u32 = de::Deserialize::deserialize(&mut *self)?;
+                u64::from(len)
+            }
+            // The NEXTINT is the length or element count that starts the
+            // member, so it is left in place for the member to read.
+            lc => {
+                let n = u64::from(self.peek_u32()?);
+                let factor = [1, 4, 8][lc as usize - 5];
+                4 + n * factor
+            }
+        };
+        let must_understand = header & EMHEADER_MUST_UNDERSTAND != 0;
+        Ok(Some((header & EMHEADER_ID_MASK, len, must_understand)))
+    }
+
+    /// Makes the current position the origin for alignment, while offsets in
+    /// errors are still counted from the start of the input.
     pub(crate) fn reset_pos(&mut self) {
+        self.base = self.offset_of(self.pos);
+        self.start = 0;
         self.pos = 0;
     }
+
+    /// Returns the offset in the input of the next byte to read.
+    pub(crate) fn offset(&self) -> u64 {
+        self.offset_of(self.pos)
+    }
+
+    /// Returns the offset in the input of the position `pos`.
+    fn offset_of(&self, pos: u64) -> u64 {
+        self.base + pos - self.start
+    }
+
+    /// Runs `f` one level of nesting deeper.
+    fn nested<T, F>(&mut self, f: F) -> Result<T>
+    where
+        F: FnOnce(&mut Self) -> Result<T>,
+    {
+        if self.depth >= self.max_depth {
+            return Err(Error::RecursionLimitExceeded);
+        }
+        self.depth += 1;
+        let result = f(self);
+        self.depth -= 1;
+        result
+    }
+
+    /// Deserializes a value with `seed`, attaching to an error the offset at
+    /// which the value starts and `segment` if error positions are enabled.
+    fn deserialize_at<T>(&mut self, seed: T, segment: Option<PathSegment>) -> Result<T::Value>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        let offset = self.offset_of(self.pos);
+        let error_positions = self.error_positions;
+        seed.deserialize(&mut *self).map_err(|e| {
+            if error_positions {
+                e.at(offset, segment)
+            } else {
+                e
+            }
+        })
+    }
+
+    /// Deserializes the top-level value, attaching to an error the offset of
+    /// the value that failed and the path leading to it if error positions
+    /// are enabled.
+    pub(crate) fn deserialize_value<T>(&mut self) -> Result<T>
+    where
+        T: de::Deserialize<'de>,
+    {
+        self.deserialize_at(PhantomData, None)
+    }
+
+    /// Checks that the input has been read entirely, failing with the number
+    /// of bytes left over otherwise. In XCDR2 the padding to a multiple of
+    /// four bytes that may end the data is not counted.
+    ///
+    /// The rest of the input is consumed, which for a stream means reading
+    /// it until it is closed.
+    pub fn end(&mut self) -> Result<()> {
+        let left = self.peeked.len() as u64 + self.reader.skip_to_end()?;
+        self.peeked.clear();
+        let padding = match self.version {
+            CdrVersion::Xcdr1 => 0,
+            CdrVersion::Xcdr2 => (4 - self.pos % 4) % 4,
+        };
+        if left > 0 && left != padding {
+            Err(Error::TrailingBytes(left))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Reads the `amt` bytes of padding that the encapsulation options declare
+    /// at the end of the data. Unless in lenient mode, they must be present
+    /// and, once the input is known to be exhausted, must have brought the
+    /// data to a multiple of four bytes.
+    pub(crate) fn read_end_padding(&mut self, amt: usize) -> Result<()> {
+        if self.padding_mode == PaddingMode::Lenient {
+            let left = self.reader.remaining_len().map(|n| n + self.peeked.len());
+            return self.read_padding(left.map_or(amt, |n| n.min(amt)));
+        }
+
+        self.read_padding(amt)?;
+        let at_end = self.peeked.is_empty() && self.reader.remaining_len() == Some(0);
+        if amt > 0 && at_end && self.pos % 4 != 0 {
+            Err(Error::InvalidEncapsulation)
+        } else {
+            Ok(())
+        }
+    }
 }
 
 macro_rules! impl_deserialize_value {
@@ -89,16 +696,18 @@ macro_rules! impl_deserialize_value {
         where
             V: de::Visitor<'de>,
         {
+            let mut buf = [0; core::mem::size_of::<$ty>()];
             self.read_padding_of::<$ty>()?;
             self.read_size_of::<$ty>()?;
-            visitor.$visitor_method(self.reader.$reader_method::<E>()?)
+            self.read_exact(&mut buf)?;
+            visitor.$visitor_method(E::$reader_method(&buf))
         }
     };
 }
 
-impl<'de, 'a, R, S, E> de::Deserializer<'de> for &'a mut Deserializer<R, S, E>
+impl<'de, R, S, E> de::Deserializer<'de> for &mut Deserializer<R, S, E>
 where
-    R: Read,
+    R: CdrRead<'de>,
     S: SizeLimit,
     E: ByteOrder,
 {
@@ -143,16 +752,20 @@ where
     where
         V: de::Visitor<'de>,
     {
+        let mut buf = [0; 1];
         self.read_size_of::<i8>()?;
-        visitor.visit_i8(self.reader.read_i8()?)
+        self.read_exact(&mut buf)?;
+        visitor.visit_i8(buf[0] as i8)
     }
 
     fn deserialize_u8<V>(self, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
+        let mut buf = [0; 1];
         self.read_size_of::<u8>()?;
-        visitor.visit_u8(self.reader.read_u8()?)
+        self.read_exact(&mut buf)?;
+        visitor.visit_u8(buf[0])
     }
 
     fn deserialize_char<V>(self, visitor: V) -> Result<V::Value>
@@ -160,7 +773,7 @@ where
         V: de::Visitor<'de>,
     {
         let mut buf = [0u8; 4];
-        self.reader.read_exact(&mut buf[..1])?;
+        self.read_exact(&mut buf[..1])?;
 
         if utf8_char_width(buf[0]) != 1 {
             Err(Error::InvalidCharEncoding)
@@ -174,35 +787,70 @@ where
     where
         V: de::Visitor<'de>,
     {
-        visitor.visit_str(&self.read_string()?)
+        match self.read_string()? {
+            Cow::Borrowed(s) => visitor.visit_borrowed_str(s),
+            Cow::Owned(s) => visitor.visit_str(&s),
+        }
     }
 
     fn deserialize_string<V>(self, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        visitor.visit_string(self.read_string()?)
+        match self.read_string()? {
+            Cow::Borrowed(s) => visitor.visit_borrowed_str(s),
+            Cow::Owned(s) => visitor.visit_string(s),
+        }
     }
 
     fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        visitor.visit_bytes(&self.read_bytes()?)
+        match self.read_bytes()? {
+            Cow::Borrowed(v) => visitor.visit_borrowed_bytes(v),
+            Cow::Owned(v) => visitor.visit_bytes(&v),
+        }
     }
 
     fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        visitor.visit_byte_buf(self.read_bytes()?)
+        match self.read_bytes()? {
+            Cow::Borrowed(v) => visitor.visit_borrowed_bytes(v),
+            Cow::Owned(v) => visitor.visit_byte_buf(v),
+        }
     }
 
-    fn deserialize_option<V>(self, _visitor: V) -> Result<V::Value>
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        Err(Error::TypeNotSupported)
+        match core::mem::replace(&mut self.optional, Optional::Value) {
+            // Absent members of mutable structs are left out altogether.
+            Optional::Parameter => self.nested(|this| visitor.visit_some(this)),
+            Optional::Member(_) if self.version == CdrVersion::Xcdr1 => {
+                match self.read_parameter_header()? {
+                    Some((_, len, _)) if len > 0 => {
+                        let end = self.pos + len;
+                        let value = self.nested(|this| visitor.visit_some(this))?;
+                        self.skip_to(end)?;
+                        Ok(value)
+                    }
+                    _ => visitor.visit_none(),
+                }
+            }
+            Optional::Member(_) | Optional::Value => {
+                let present: bool = de::Deserialize::deserialize(&mut *self)?;
+                if present {
+                    self.nested(|this| visitor.visit_some(this))
+                } else {
+                    visitor.visit_none()
+                }
+            }
+            Optional::Absent => Err(Error::TypeNotSupported),
+        }
     }
 
     fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value>
@@ -219,65 +867,66 @@ where
         visitor.visit_unit()
     }
 
-    fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
+    fn deserialize_newtype_struct<V>(self, name: &'static str, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        visitor.visit_newtype_struct(self)
+        match name {
+            ENCAPSULATED_NAME => self.read_encapsulated(visitor),
+            WCHAR_NAME => {
+                let c = self.read_wchar()?;
+                visitor.visit_newtype_struct(c.into_deserializer())
+            }
+            WSTRING_NAME => {
+                let s = self.read_wstring()?;
+                visitor.visit_newtype_struct(s.into_deserializer())
+            }
+            DELIMITED_NAME => {
+                self.delimit = self.version == CdrVersion::Xcdr2;
+                let value = self.nested(|this| visitor.visit_newtype_struct(this))?;
+                self.check_unwrapped()?;
+                Ok(value)
+            }
+            _ if extensibility_of(name).is_some() => {
+                self.annotated = extensibility_of(name);
+                let value = self.nested(|this| visitor.visit_newtype_struct(this))?;
+                self.check_unwrapped()?;
+                Ok(value)
+            }
+            _ => match Bulk::of(name) {
+                Some(Bulk::Sequence(size)) => {
+                    let len: u32 = de::Deserialize::deserialize(&mut *self)?;
+                    self.read_bulk(size, len as usize, visitor)
+                }
+                Some(Bulk::Array(size)) => {
+                    self.bulk_array = Some(size);
+                    let result = visitor.visit_newtype_struct(&mut *self);
+                    self.bulk_array = None;
+                    result
+                }
+                None => self.nested(|this| visitor.visit_newtype_struct(this)),
+            },
+        }
     }
 
     fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
+        let end = self.begin_collection()?;
         let len: u32 = de::Deserialize::deserialize(&mut *self)?;
-        self.deserialize_tuple(len as usize, visitor)
+        self.read_elements(len as usize, end, visitor)
     }
 
     fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        struct Access<'a, R, S, E>
-        where
-            R: Read + 'a,
-            S: SizeLimit + 'a,
-            E: ByteOrder + 'a,
-        {
-            deserializer: &'a mut Deserializer<R, S, E>,
-            len: usize,
+        if let Some(size) = self.bulk_array.take() {
+            return self.read_bulk(size, len, visitor);
         }
-
-        impl<'de, 'a, R, S, E> de::SeqAccess<'de> for Access<'a, R, S, E>
-        where
-            R: Read + 'a,
-            S: SizeLimit,
-            E: ByteOrder,
-        {
-            type Error = Error;
-
-            fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
-            where
-                T: de::DeserializeSeed<'de>,
-            {
-                if self.len > 0 {
-                    self.len -= 1;
-                    let value = de::DeserializeSeed::deserialize(seed, &mut *self.deserializer)?;
-                    Ok(Some(value))
-                } else {
-                    Ok(None)
-                }
-            }
-
-            fn size_hint(&self) -> Option<usize> {
-                Some(self.len)
-            }
-        }
-
-        visitor.visit_seq(Access {
-            deserializer: self,
-            len,
-        })
+        let end = self.begin_collection()?;
+        self.read_elements(len, end, visitor)
     }
 
     fn deserialize_tuple_struct<V>(
@@ -289,26 +938,89 @@ where
     where
         V: de::Visitor<'de>,
     {
-        self.deserialize_tuple(len, visitor)
+        self.check_unwrapped()?;
+        self.read_elements(len, None, visitor)
     }
 
-    fn deserialize_map<V>(self, _visitor: V) -> Result<V::Value>
+    fn deserialize_map<V>(self, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        Err(Error::TypeNotSupported)
+        let end = self.begin_collection()?;
+        let len: u32 = de::Deserialize::deserialize(&mut *self)?;
+        let value = self.nested(|this| {
+            visitor.visit_map(MapAccess {
+                deserializer: this,
+                len: len as usize,
+                index: 0,
+            })
+        })?;
+        if let Some(end) = end {
+            self.skip_to(end)?;
+        }
+        Ok(value)
     }
 
     fn deserialize_struct<V>(
         self,
-        _name: &'static str,
+        name: &'static str,
         fields: &'static [&'static str],
         visitor: V,
     ) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        self.deserialize_tuple(fields.len(), visitor)
+        let annotated = self.annotated.take();
+        self.check_unwrapped()?;
+        let extensibility = self.extensibility.of_struct(annotated, self.in_aggregate);
+        let delimited = self.version == CdrVersion::Xcdr2 && extensibility != Extensibility::Final;
+        let parameter_list = extensibility == Extensibility::Mutable;
+        self.aggregate(|this| {
+            if !delimited {
+                if parameter_list {
+                    return visitor.visit_map(ParameterListAccess {
+                        deserializer: this,
+                        name,
+                        fields,
+                        index: 0,
+                        end: 0,
+                        struct_end: None,
+                    });
+                }
+                return visitor.visit_seq(ElementAccess {
+                    deserializer: this,
+                    len: fields.len(),
+                    index: 0,
+                    fields: Some((name, fields)),
+                    member_ids: MemberIds::default(),
+                });
+            }
+
+            let size: u32 = de::Deserialize::deserialize(&mut *this)?;
+            let end = this.pos + u64::from(size);
+            let value = if parameter_list {
+                visitor.visit_map(ParameterListAccess {
+                    deserializer: &mut *this,
+                    name,
+                    fields,
+                    index: 0,
+                    end: 0,
+                    struct_end: Some(end),
+                })?
+            } else {
+                visitor.visit_seq(DelimitedAccess {
+                    deserializer: &mut *this,
+                    name,
+                    fields,
+                    index: 0,
+                    member_ids: MemberIds::default(),
+                    end,
+                })?
+            };
+            // Skips members appended by a newer peer.
+            this.skip_to(end)?;
+            Ok(value)
+        })
     }
 
     fn deserialize_enum<V>(
@@ -320,26 +1032,7 @@ where
     where
         V: de::Visitor<'de>,
     {
-        impl<'de, 'a, R, S, E> de::EnumAccess<'de> for &'a mut Deserializer<R, S, E>
-        where
-            R: Read + 'a,
-            S: SizeLimit,
-            E: ByteOrder,
-        {
-            type Error = Error;
-            type Variant = Self;
-
-            fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self::Variant)>
-            where
-                V: de::DeserializeSeed<'de>,
-            {
-                let idx: u32 = de::Deserialize::deserialize(&mut *self)?;
-                let val: Result<_> = seed.deserialize(idx.into_deserializer());
-                Ok((val?, self))
-            }
-        }
-
-        visitor.visit_enum(self)
+        self.aggregate(|this| visitor.visit_enum(this))
     }
 
     fn deserialize_identifier<V>(self, _visitor: V) -> Result<V::Value>
@@ -361,9 +1054,246 @@ where
     }
 }
 
-impl<'de, 'a, R, S, E> de::VariantAccess<'de> for &'a mut Deserializer<R, S, E>
+/// Reads the elements of a sequence, an array or a tuple, or the fields of a
+/// struct if `fields` holds its name and field names.
+struct ElementAccess<'a, R, S, E>
+where
+    R: 'a,
+    S: SizeLimit + 'a,
+    E: ByteOrder + 'a,
+{
+    deserializer: &'a mut Deserializer<R, S, E>,
+    len: usize,
+    index: usize,
+    fields: Option<(&'static str, &'static [&'static str])>,
+    member_ids: MemberIds,
+}
+
+impl<'de, 'a, R, S, E> de::SeqAccess<'de> for ElementAccess<'a, R, S, E>
+where
+    R: CdrRead<'de> + 'a,
+    S: SizeLimit,
+    E: ByteOrder,
+{
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        if self.len == 0 {
+            return Ok(None);
+        }
+
+        self.len -= 1;
+        let segment = match self.fields {
+            Some((name, fields)) => {
+                let id = self.member_ids.next(fields[self.index]);
+                self.deserializer.optional = Optional::Member(id);
+                field_segment(name, fields, self.index)
+            }
+            None => {
+                self.deserializer.optional = Optional::Value;
+                PathSegment::Index(self.index)
+            }
+        };
+        self.index += 1;
+        let value = self.deserializer.deserialize_at(seed, Some(segment));
+        self.deserializer.optional = Optional::Value;
+        Ok(Some(value?))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.deserializer.cautious_len(self.len))
+    }
+}
+
+/// Reads the fields of a struct prefixed with a DHEADER, which end at `end`.
+struct DelimitedAccess<'a, R, S, E>
+where
+    R: 'a,
+    S: SizeLimit + 'a,
+    E: ByteOrder + 'a,
+{
+    deserializer: &'a mut Deserializer<R, S, E>,
+    name: &'static str,
+    fields: &'static [&'static str],
+    index: usize,
+    member_ids: MemberIds,
+    end: u64,
+}
+
+impl<'de, 'a, R, S, E> de::SeqAccess<'de> for DelimitedAccess<'a, R, S, E>
+where
+    R: CdrRead<'de> + 'a,
+    S: SizeLimit,
+    E: ByteOrder,
+{
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        // Members missing from data written by an older peer are reported as
+        // the end of the sequence.
+        if self.index < self.fields.len() && self.deserializer.pos < self.end {
+            let id = self.member_ids.next(self.fields[self.index]);
+            let segment = field_segment(self.name, self.fields, self.index);
+            self.index += 1;
+            self.deserializer.optional = Optional::Member(id);
+            let value = self.deserializer.deserialize_at(seed, Some(segment));
+            self.deserializer.optional = Optional::Value;
+            let value = value?;
+            if self.deserializer.pos > self.end {
+                return Err(Error::LengthExceeded);
+            }
+            Ok(Some(value))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.fields.len() - self.index)
+    }
+}
+
+/// Reads the key/value pairs of a map, which are preceded by their count.
+struct MapAccess<'a, R, S, E>
+where
+    R: 'a,
+    S: SizeLimit + 'a,
+    E: ByteOrder + 'a,
+{
+    deserializer: &'a mut Deserializer<R, S, E>,
+    len: usize,
+    index: usize,
+}
+
+impl<'de, 'a, R, S, E> de::MapAccess<'de> for MapAccess<'a, R, S, E>
+where
+    R: CdrRead<'de> + 'a,
+    S: SizeLimit,
+    E: ByteOrder,
+{
+    type Error = Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>>
+    where
+        K: de::DeserializeSeed<'de>,
+    {
+        if self.len > 0 {
+            self.len -= 1;
+            let segment = PathSegment::Index(self.index);
+            self.deserializer
+                .deserialize_at(seed, Some(segment))
+                .map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value>
+    where
+        V: de::DeserializeSeed<'de>,
+    {
+        let segment = PathSegment::Index(self.index);
+        self.index += 1;
+        self.deserializer.deserialize_at(seed, Some(segment))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.deserializer.cautious_len(self.len))
+    }
+}
+
+/// Reads the members of a mutable struct, each preceded by a parameter header
+/// or, if `struct_end` is given, by an EMHEADER.
+struct ParameterListAccess<'a, R, S, E>
+where
+    R: 'a,
+    S: SizeLimit + 'a,
+    E: ByteOrder + 'a,
+{
+    deserializer: &'a mut Deserializer<R, S, E>,
+    name: &'static str,
+    fields: &'static [&'static str],
+    index: usize,
+    end: u64,
+    struct_end: Option<u64>,
+}
+
+impl<'de, 'a, R, S, E> de::MapAccess<'de> for ParameterListAccess<'a, R, S, E>
+where
+    R: CdrRead<'de> + 'a,
+    S: SizeLimit,
+    E: ByteOrder,
+{
+    type Error = Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>>
+    where
+        K: de::DeserializeSeed<'de>,
+    {
+        loop {
+            let header = match self.struct_end {
+                Some(end) => self.deserializer.read_emheader(end)?,
+                None => self.deserializer.read_parameter_header()?,
+            };
+            let (id, len, must_understand) = match header {
+                Some(header) => header,
+                None => return Ok(None),
+            };
+            self.end = self.deserializer.pos + len;
+            match find_member(self.fields, id) {
+                Some(index) => {
+                    self.index = index;
+                    let key: Result<_> = seed.deserialize(self.fields[index].into_deserializer());
+                    return key.map(Some);
+                }
+                None if must_understand => return Err(Error::UnknownMustUnderstand(id)),
+                // Skips members unknown to this version of the type.
+                None => self.deserializer.skip_to(self.end)?,
+            }
+        }
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value>
+    where
+        V: de::DeserializeSeed<'de>,
+    {
+        self.deserializer.optional = Optional::Parameter;
+        let segment = field_segment(self.name, self.fields, self.index);
+        let value = self.deserializer.deserialize_at(seed, Some(segment))?;
+        self.deserializer.optional = Optional::Value;
+        self.deserializer.skip_to(self.end)?;
+        Ok(value)
+    }
+}
+
+impl<'de, R, S, E> de::EnumAccess<'de> for &mut Deserializer<R, S, E>
+where
+    R: CdrRead<'de>,
+    S: SizeLimit,
+    E: ByteOrder,
+{
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self::Variant)>
+    where
+        V: de::DeserializeSeed<'de>,
+    {
+        let idx: u32 = de::Deserialize::deserialize(&mut *self)?;
+        let val: Result<_> = seed.deserialize(idx.into_deserializer());
+        Ok((val?, self))
+    }
+}
+
+impl<'de, R, S, E> de::VariantAccess<'de> for &mut Deserializer<R, S, E>
 where
-    R: Read,
+    R: CdrRead<'de>,
     S: SizeLimit,
     E: ByteOrder,
 {
@@ -384,14 +1314,14 @@ where
     where
         V: de::Visitor<'de>,
     {
-        de::Deserializer::deserialize_tuple(self, len, visitor)
+        self.read_elements(len, None, visitor)
     }
 
     fn struct_variant<V>(self, fields: &'static [&'static str], visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        de::Deserializer::deserialize_tuple(self, fields.len(), visitor)
+        self.read_elements(fields.len(), None, visitor)
     }
 }
 
@@ -400,12 +1330,40 @@ impl<R, S> From<Deserializer<R, S, BigEndian>> for Deserializer<R, S, LittleEndi
         Self {
             reader: t.reader,
             size_limit: t.size_limit,
+            base: t.base,
+            start: t.start,
             pos: t.pos,
+            version: t.version,
+            extensibility: t.extensibility,
+            in_aggregate: t.in_aggregate,
+            peeked: t.peeked,
+            optional: t.optional,
+            delimit: t.delimit,
+            annotated: t.annotated,
+            bulk_array: t.bulk_array,
+            ascii_only: t.ascii_only,
+            wide_encoding: t.wide_encoding,
+            max_preallocation: t.max_preallocation,
+            padding_mode: t.padding_mode,
+            error_positions: t.error_positions,
+            depth: t.depth,
+            max_depth: t.max_depth,
             phantom: PhantomData,
         }
     }
 }
 
+fn field_segment(
+    struct_name: &'static str,
+    fields: &'static [&'static str],
+    index: usize,
+) -> PathSegment {
+    PathSegment::Field {
+        struct_name,
+        field: field_name(fields[index]),
+    }
+}
+
 #[inline]
 fn utf8_char_width(first_byte: u8) -> usize {
     UTF8_CHAR_WIDTH[first_byte as usize] as usize
@@ -432,22 +1390,60 @@ const UTF8_CHAR_WIDTH: &[u8; 256] = &[
 ];
 
 /// Deserializes a slice of bytes into an object.
-pub fn deserialize_data<'de, T, E>(bytes: &[u8]) -> Result<T>
+pub fn deserialize_data<'de, T, E>(bytes: &'de [u8]) -> Result<T>
+where
+    T: de::Deserialize<'de>,
+    E: ByteOrder,
+{
+    let mut deserializer = Deserializer::<_, _, E>::from_slice(bytes, Infinite);
+    deserializer.deserialize_value()
+}
+
+/// Deserializes a slice of bytes without an encapsulation header into an
+/// object, reading it with the byte order, version and extensibility of the
+/// encapsulation given at runtime.
+pub fn deserialize_data_with<'de, T>(bytes: &'de [u8], kind: EncapsulationKind) -> Result<T>
+where
+    T: de::Deserialize<'de>,
+{
+    match kind.endianness() {
+        Endianness::Big => deserialize_data_as::<_, BigEndian>(bytes, kind),
+        Endianness::Little => deserialize_data_as::<_, LittleEndian>(bytes, kind),
+    }
+}
+
+fn deserialize_data_as<'de, T, E>(bytes: &'de [u8], kind: EncapsulationKind) -> Result<T>
+where
+    T: de::Deserialize<'de>,
+    E: ByteOrder,
+{
+    let mut deserializer = Deserializer::<_, _, E>::from_slice(bytes, Infinite)
+        .with_version(kind.version())
+        .with_extensibility(kind.extensibility());
+    deserializer.deserialize_value()
+}
+
+/// Deserializes a slice of bytes into an object, failing if any bytes are
+/// left over.
+pub fn deserialize_data_strict<'de, T, E>(bytes: &'de [u8]) -> Result<T>
 where
     T: de::Deserialize<'de>,
     E: ByteOrder,
 {
-    deserialize_data_from::<_, _, _, E>(bytes, Infinite)
+    let mut deserializer = Deserializer::<_, _, E>::from_slice(bytes, Infinite);
+    let value = deserializer.deserialize_value()?;
+    deserializer.end()?;
+    Ok(value)
 }
 
-/// Deserializes an object directly from a `Read`.
+/// Deserializes an object directly from a `CdrRead`.
 pub fn deserialize_data_from<'de, R, T, S, E>(reader: R, size_limit: S) -> Result<T>
 where
-    R: Read,
+    R: CdrRead<'de>,
     T: de::Deserialize<'de>,
     S: SizeLimit,
     E: ByteOrder,
 {
     let mut deserializer = Deserializer::<_, S, E>::new(reader, size_limit);
-    de::Deserialize::deserialize(&mut deserializer)
+    deserializer.deserialize_value()
 }
@@ -0,0 +1,209 @@
+//! Sources of bytes for the deserializer.
+
+#[cfg(feature = "std")]
+use std::io;
+
+use crate::error::{Error, Result};
+
+/// A source of bytes for the `Deserializer`.
+///
+/// It is implemented for `SliceReader`, which lends out bytes borrowed from its
+/// input so that `&'de str` and `&'de [u8]` can be deserialized without
+/// copying, and which is the only reader reporting the length of what is left.
+/// With the `std` feature it is also implemented for every `std::io::Read`,
+/// and without it for `&[u8]` and mutable references to readers.
+pub trait CdrRead<'de> {
+    /// Fills `buf` with the next bytes.
+    fn read_into(&mut self, buf: &mut [u8]) -> Result<()>;
+
+    /// Reads the next `len` bytes as a slice borrowed from the input, or
+    /// returns `None` without consuming anything if the input cannot lend
+    /// them.
+    fn read_borrowed(&mut self, _len: usize) -> Result<Option<&'de [u8]>> {
+        Ok(None)
+    }
+
+    /// Skips the next `len` bytes.
+    fn skip(&mut self, len: u64) -> Result<()>;
+
+    /// Consumes the rest of the input, returning its length.
+    fn skip_to_end(&mut self) -> Result<u64>;
+
+    /// Returns the number of bytes left in the input, if known.
+    fn remaining_len(&self) -> Option<usize> {
+        None
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'de, R> CdrRead<'de> for R
+where
+    R: io::Read,
+{
+    fn read_into(&mut self, buf: &mut [u8]) -> Result<()> {
+        self.read_exact(buf).map_err(Into::into)
+    }
+
+    fn skip(&mut self, len: u64) -> Result<()> {
+        let skipped = io::copy(&mut io::Read::take(self, len), &mut io::sink())?;
+        if skipped < len {
+            Err(unexpected_eof())
+        } else {
+            Ok(())
+        }
+    }
+
+    fn skip_to_end(&mut self) -> Result<u64> {
+        io::copy(self, &mut io::sink()).map_err(Into::into)
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl<'de, 'a> CdrRead<'de> for &'a [u8] {
+    fn read_into(&mut self, buf: &mut [u8]) -> Result<()> {
+        let mut reader = SliceReader::new(self);
+        reader.read_into(buf)?;
+        *self = reader.remaining();
+        Ok(())
+    }
+
+    fn skip(&mut self, len: u64) -> Result<()> {
+        let mut reader = SliceReader::new(self);
+        reader.skip(len)?;
+        *self = reader.remaining();
+        Ok(())
+    }
+
+    fn skip_to_end(&mut self) -> Result<u64> {
+        let len = self.len();
+        *self = &[];
+        Ok(len as u64)
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl<'de, R> CdrRead<'de> for &mut R
+where
+    R: CdrRead<'de> + ?Sized,
+{
+    fn read_into(&mut self, buf: &mut [u8]) -> Result<()> {
+        (**self).read_into(buf)
+    }
+
+    fn read_borrowed(&mut self, len: usize) -> Result<Option<&'de [u8]>> {
+        (**self).read_borrowed(len)
+    }
+
+    fn skip(&mut self, len: u64) -> Result<()> {
+        (**self).skip(len)
+    }
+
+    fn skip_to_end(&mut self) -> Result<u64> {
+        (**self).skip_to_end()
+    }
+
+    fn remaining_len(&self) -> Option<usize> {
+        (**self).remaining_len()
+    }
+}
+
+/// A reader over a byte slice that lends out the bytes it reads.
+#[derive(Clone, Copy, Debug)]
+pub struct SliceReader<'de> {
+    slice: &'de [u8],
+}
+
+impl<'de> SliceReader<'de> {
+    pub fn new(slice: &'de [u8]) -> Self {
+        Self { slice }
+    }
+
+    /// Returns the bytes that have not been read yet.
+    pub fn remaining(&self) -> &'de [u8] {
+        self.slice
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'de [u8]> {
+        if len > self.slice.len() {
+            return Err(unexpected_eof());
+        }
+
+        let (taken, rest) = self.slice.split_at(len);
+        self.slice = rest;
+        Ok(taken)
+    }
+}
+
+impl<'de> CdrRead<'de> for SliceReader<'de> {
+    fn read_into(&mut self, buf: &mut [u8]) -> Result<()> {
+        buf.copy_from_slice(self.take(buf.len())?);
+        Ok(())
+    }
+
+    fn read_borrowed(&mut self, len: usize) -> Result<Option<&'de [u8]>> {
+        self.take(len).map(Some)
+    }
+
+    fn skip(&mut self, len: u64) -> Result<()> {
+        let len = usize::try_from(len).map_err(|_| unexpected_eof())?;
+        self.take(len).map(|_| ())
+    }
+
+    fn skip_to_end(&mut self) -> Result<u64> {
+        let len = self.slice.len();
+        self.slice = &[];
+        Ok(len as u64)
+    }
+
+    fn remaining_len(&self) -> Option<usize> {
+        Some(self.slice.len())
+    }
+}
+
+/// A reader borrowed as a trait object, so that a deserializer created over it
+/// has the same type however many times this is repeated.
+pub(crate) struct DynReader<'a, 'de>(pub(crate) &'a mut dyn CdrRead<'de>);
+
+impl<'a, 'de> CdrRead<'de> for DynReader<'a, 'de> {
+    fn read_into(&mut self, buf: &mut [u8]) -> Result<()> {
+        self.0.read_into(buf)
+    }
+
+    fn read_borrowed(&mut self, len: usize) -> Result<Option<&'de [u8]>> {
+        self.0.read_borrowed(len)
+    }
+
+    fn skip(&mut self, len: u64) -> Result<()> {
+        self.0.skip(len)
+    }
+
+    fn skip_to_end(&mut self) -> Result<u64> {
+        self.0.skip_to_end()
+    }
+
+    fn remaining_len(&self) -> Option<usize> {
+        self.0.remaining_len()
+    }
+}
+
+pub(crate) fn unexpected_eof() -> Error {
+    Error::UnexpectedEof
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slice_reader() {
+        let mut reader = SliceReader::new(&[1, 2, 3, 4, 5]);
+        let mut buf = [0; 2];
+        reader.read_into(&mut buf).unwrap();
+        assert_eq!(buf, [1, 2]);
+        assert_eq!(reader.remaining_len(), Some(3));
+        assert_eq!(reader.read_borrowed(2).unwrap(), Some(&[3, 4][..]));
+        assert!(reader.skip(2).is_err());
+        assert_eq!(reader.skip_to_end().unwrap(), 1);
+        assert!(reader.remaining().is_empty());
+    }
+}
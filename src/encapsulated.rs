@@ -0,0 +1,122 @@
+//! CORBA encapsulations nested in CDR data.
+
+use core::{
+    fmt,
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+};
+
+use byteorder::ByteOrder;
+use serde::{de, ser};
+
+pub(crate) const ENCAPSULATED_NAME: &str = "$cdr::Encapsulated";
+
+/// Byte-order flag starting an encapsulation written in big-endian byte order.
+pub(crate) const FLAG_BE: u8 = 0;
+
+/// Byte-order flag starting an encapsulation written in little-endian byte
+/// order.
+pub(crate) const FLAG_LE: u8 = 1;
+
+/// Returns the byte-order flag for the byte order `E`.
+pub(crate) fn byte_order_flag<E>() -> u8
+where
+    E: ByteOrder,
+{
+    if E::read_u16(&[0, 1]) == 1 {
+        FLAG_BE
+    } else {
+        FLAG_LE
+    }
+}
+
+/// A value in a CORBA encapsulation, as used for type codes, IOR profiles and
+/// service contexts.
+///
+/// It is written as a `sequence<octet>` whose first octet is a byte-order
+/// flag, followed by the value in that byte order with alignment relative to
+/// the start of the sequence. The `Serializer` uses its own byte order, while
+/// the `Deserializer` reads the value in whichever byte order the flag gives.
+/// Other formats see a newtype around the value.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Encapsulated<T>(pub T);
+
+impl<T> From<T> for Encapsulated<T> {
+    fn from(value: T) -> Self {
+        Encapsulated(value)
+    }
+}
+
+impl<T> Deref for Encapsulated<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Encapsulated<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+impl<T> ser::Serialize for Encapsulated<T>
+where
+    T: ser::Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        serializer.serialize_newtype_struct(ENCAPSULATED_NAME, &self.0)
+    }
+}
+
+impl<'de, T> de::Deserialize<'de> for Encapsulated<T>
+where
+    T: de::Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> core::result::Result<Self, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        struct Visitor<T>(PhantomData<T>);
+
+        impl<'de, T> de::Visitor<'de> for Visitor<T>
+        where
+            T: de::Deserialize<'de>,
+        {
+            type Value = Encapsulated<T>;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("an encapsulation")
+            }
+
+            fn visit_newtype_struct<D>(
+                self,
+                deserializer: D,
+            ) -> core::result::Result<Encapsulated<T>, D::Error>
+            where
+                D: de::Deserializer<'de>,
+            {
+                de::Deserialize::deserialize(deserializer).map(Encapsulated)
+            }
+        }
+
+        deserializer.deserialize_newtype_struct(ENCAPSULATED_NAME, Visitor(PhantomData))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use byteorder::{BigEndian, LittleEndian};
+
+    use super::*;
+
+    #[test]
+    fn test_byte_order_flag() {
+        assert_eq!(byte_order_flag::<BigEndian>(), FLAG_BE);
+        assert_eq!(byte_order_flag::<LittleEndian>(), FLAG_LE);
+    }
+}
@@ -1,12 +1,90 @@
 use byteorder::{BigEndian, ByteOrder, LittleEndian};
 
+use crate::error::{Error, Result};
+
 pub const ENCAPSULATION_HEADER_SIZE: u64 = 4;
 
+/// Bits of the second options byte holding the number of padding bytes
+/// appended to the data.
+pub(crate) const OPTIONS_PADDING_MASK: u8 = 0x03;
+
+/// Versions of the CDR data representation.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum CdrVersion {
+    /// Classic OMG CDR, also known as XCDR version 1.
+    Xcdr1,
+    /// Extended CDR version 2 defined by DDS-XTypes 1.3.
+    Xcdr2,
+}
+
+impl CdrVersion {
+    /// Returns the largest alignment applied to primitive types.
+    pub(crate) fn max_alignment(self) -> usize {
+        match self {
+            CdrVersion::Xcdr1 => 8,
+            CdrVersion::Xcdr2 => 4,
+        }
+    }
+}
+
+impl Default for CdrVersion {
+    fn default() -> Self {
+        CdrVersion::Xcdr1
+    }
+}
+
+/// Extensibility kinds of aggregated types.
+///
+/// The extensibility kind of an encapsulation applies to the outermost
+/// structs, those not nested in another struct or in an enum, while nested
+/// structs are final. A struct wrapped in `Final`, `Appendable` or `Mutable`
+/// has that kind wherever it appears, like the IDL annotations of the same
+/// names.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Extensibility {
+    /// Members are written back to back with no additional headers.
+    Final,
+    /// Members may be appended by later versions of a type. In XCDR2 the
+    /// struct is prefixed with a DHEADER holding its serialized length.
+    Appendable,
+    /// Members may be added, removed or reordered. Every struct member is
+    /// written with a header holding its member id and length: a parameter
+    /// header in XCDR1, an EMHEADER in XCDR2 where the struct is also prefixed
+    /// with a DHEADER.
+    ///
+    /// Member ids are assigned sequentially from zero in declaration order. A
+    /// field renamed to `name@id`, with `id` in decimal or `0x`-prefixed
+    /// hexadecimal, uses `id` instead, like the IDL `@id` annotation. A member
+    /// wrapped in `Key` has the must-understand flag of its header set.
+    Mutable,
+}
+
+impl Extensibility {
+    /// Returns the extensibility kind of a struct, which is `annotated` if a
+    /// wrapper gives one, given that this kind applies to the outermost
+    /// structs.
+    pub(crate) fn of_struct(self, annotated: Option<Extensibility>, nested: bool) -> Extensibility {
+        match annotated {
+            Some(extensibility) => extensibility,
+            None if nested => Extensibility::Final,
+            None => self,
+        }
+    }
+}
+
+impl Default for Extensibility {
+    fn default() -> Self {
+        Extensibility::Final
+    }
+}
+
 /// Data encapsulation scheme identifiers.
 pub trait Encapsulation {
     type E: ByteOrder;
     const ID: [u8; 2];
     const OPTION: [u8; 2] = [0; 2];
+    const VERSION: CdrVersion = CdrVersion::Xcdr1;
+    const EXTENSIBILITY: Extensibility = Extensibility::Final;
 }
 
 /// OMG CDR big-endian encapsulation.
@@ -34,6 +112,7 @@ pub enum PlCdrBe {}
 impl Encapsulation for PlCdrBe {
     type E = BigEndian;
     const ID: [u8; 2] = [0, 2];
+    const EXTENSIBILITY: Extensibility = Extensibility::Mutable;
 }
 
 /// ParameterList encapsulated using OMG CDR little-endian encapsulation.
@@ -43,6 +122,236 @@ pub enum PlCdrLe {}
 impl Encapsulation for PlCdrLe {
     type E = LittleEndian;
     const ID: [u8; 2] = [0, 3];
+    const EXTENSIBILITY: Extensibility = Extensibility::Mutable;
+}
+
+/// Extended CDR version 2 big-endian encapsulation.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Cdr2Be {}
+
+impl Encapsulation for Cdr2Be {
+    type E = BigEndian;
+    const ID: [u8; 2] = [0, 6];
+    const VERSION: CdrVersion = CdrVersion::Xcdr2;
+}
+
+/// Extended CDR version 2 little-endian encapsulation.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Cdr2Le {}
+
+impl Encapsulation for Cdr2Le {
+    type E = LittleEndian;
+    const ID: [u8; 2] = [0, 7];
+    const VERSION: CdrVersion = CdrVersion::Xcdr2;
+}
+
+/// Delimited extended CDR version 2 big-endian encapsulation.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum DCdr2Be {}
+
+impl Encapsulation for DCdr2Be {
+    type E = BigEndian;
+    const ID: [u8; 2] = [0, 8];
+    const VERSION: CdrVersion = CdrVersion::Xcdr2;
+    const EXTENSIBILITY: Extensibility = Extensibility::Appendable;
+}
+
+/// Delimited extended CDR version 2 little-endian encapsulation.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum DCdr2Le {}
+
+impl Encapsulation for DCdr2Le {
+    type E = LittleEndian;
+    const ID: [u8; 2] = [0, 9];
+    const VERSION: CdrVersion = CdrVersion::Xcdr2;
+    const EXTENSIBILITY: Extensibility = Extensibility::Appendable;
+}
+
+/// Extended CDR version 2 ParameterList big-endian encapsulation.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum PlCdr2Be {}
+
+impl Encapsulation for PlCdr2Be {
+    type E = BigEndian;
+    const ID: [u8; 2] = [0, 0x0a];
+    const VERSION: CdrVersion = CdrVersion::Xcdr2;
+    const EXTENSIBILITY: Extensibility = Extensibility::Mutable;
+}
+
+/// Extended CDR version 2 ParameterList little-endian encapsulation.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum PlCdr2Le {}
+
+impl Encapsulation for PlCdr2Le {
+    type E = LittleEndian;
+    const ID: [u8; 2] = [0, 0x0b];
+    const VERSION: CdrVersion = CdrVersion::Xcdr2;
+    const EXTENSIBILITY: Extensibility = Extensibility::Mutable;
+}
+
+/// Byte orders of encoded data.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Endianness {
+    /// Most significant byte first.
+    Big,
+    /// Least significant byte first.
+    Little,
+}
+
+/// Encapsulation schemes selected at runtime, mirroring the types
+/// implementing `Encapsulation`.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum EncapsulationKind {
+    /// OMG CDR big-endian encapsulation, as `CdrBe`.
+    CdrBe,
+    /// OMG CDR little-endian encapsulation, as `CdrLe`.
+    CdrLe,
+    /// ParameterList big-endian encapsulation, as `PlCdrBe`.
+    PlCdrBe,
+    /// ParameterList little-endian encapsulation, as `PlCdrLe`.
+    PlCdrLe,
+    /// Extended CDR version 2 big-endian encapsulation, as `Cdr2Be`.
+    Cdr2Be,
+    /// Extended CDR version 2 little-endian encapsulation, as `Cdr2Le`.
+    Cdr2Le,
+    /// Delimited extended CDR version 2 big-endian encapsulation, as
+    /// `DCdr2Be`.
+    DCdr2Be,
+    /// Delimited extended CDR version 2 little-endian encapsulation, as
+    /// `DCdr2Le`.
+    DCdr2Le,
+    /// Extended CDR version 2 ParameterList big-endian encapsulation, as
+    /// `PlCdr2Be`.
+    PlCdr2Be,
+    /// Extended CDR version 2 ParameterList little-endian encapsulation, as
+    /// `PlCdr2Le`.
+    PlCdr2Le,
+}
+
+impl EncapsulationKind {
+    /// Returns the kind identified by the first two bytes of an encapsulation
+    /// header, if known.
+    pub fn from_id(id: [u8; 2]) -> Option<Self> {
+        let kind = match id {
+            CdrBe::ID => EncapsulationKind::CdrBe,
+            CdrLe::ID => EncapsulationKind::CdrLe,
+            PlCdrBe::ID => EncapsulationKind::PlCdrBe,
+            PlCdrLe::ID => EncapsulationKind::PlCdrLe,
+            Cdr2Be::ID => EncapsulationKind::Cdr2Be,
+            Cdr2Le::ID => EncapsulationKind::Cdr2Le,
+            DCdr2Be::ID => EncapsulationKind::DCdr2Be,
+            DCdr2Le::ID => EncapsulationKind::DCdr2Le,
+            PlCdr2Be::ID => EncapsulationKind::PlCdr2Be,
+            PlCdr2Le::ID => EncapsulationKind::PlCdr2Le,
+            _ => return None,
+        };
+        Some(kind)
+    }
+
+    /// Returns the identifier written in the first two bytes of the
+    /// encapsulation header.
+    pub fn id(self) -> [u8; 2] {
+        match self {
+            EncapsulationKind::CdrBe => CdrBe::ID,
+            EncapsulationKind::CdrLe => CdrLe::ID,
+            EncapsulationKind::PlCdrBe => PlCdrBe::ID,
+            EncapsulationKind::PlCdrLe => PlCdrLe::ID,
+            EncapsulationKind::Cdr2Be => Cdr2Be::ID,
+            EncapsulationKind::Cdr2Le => Cdr2Le::ID,
+            EncapsulationKind::DCdr2Be => DCdr2Be::ID,
+            EncapsulationKind::DCdr2Le => DCdr2Le::ID,
+            EncapsulationKind::PlCdr2Be => PlCdr2Be::ID,
+            EncapsulationKind::PlCdr2Le => PlCdr2Le::ID,
+        }
+    }
+
+    /// Returns the byte order of the encoded data.
+    pub fn endianness(self) -> Endianness {
+        if self.id()[1] & 1 == 0 {
+            Endianness::Big
+        } else {
+            Endianness::Little
+        }
+    }
+
+    /// Returns the version of the CDR representation.
+    pub fn version(self) -> CdrVersion {
+        match self {
+            EncapsulationKind::CdrBe
+            | EncapsulationKind::CdrLe
+            | EncapsulationKind::PlCdrBe
+            | EncapsulationKind::PlCdrLe => CdrVersion::Xcdr1,
+            _ => CdrVersion::Xcdr2,
+        }
+    }
+
+    /// Returns the extensibility kind applied to the outermost structs.
+    pub fn extensibility(self) -> Extensibility {
+        match self {
+            EncapsulationKind::CdrBe
+            | EncapsulationKind::CdrLe
+            | EncapsulationKind::Cdr2Be
+            | EncapsulationKind::Cdr2Le => Extensibility::Final,
+            EncapsulationKind::DCdr2Be | EncapsulationKind::DCdr2Le => Extensibility::Appendable,
+            EncapsulationKind::PlCdrBe
+            | EncapsulationKind::PlCdrLe
+            | EncapsulationKind::PlCdr2Be
+            | EncapsulationKind::PlCdr2Le => Extensibility::Mutable,
+        }
+    }
+}
+
+/// The header preceding encapsulated data.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct EncapsulationHeader {
+    /// The encapsulation scheme named by the representation identifier.
+    pub kind: EncapsulationKind,
+    /// The representation options.
+    pub options: [u8; 2],
+}
+
+impl From<EncapsulationKind> for EncapsulationHeader {
+    /// Returns a header of `kind` with no options set.
+    fn from(kind: EncapsulationKind) -> Self {
+        Self {
+            kind,
+            options: [0; 2],
+        }
+    }
+}
+
+impl EncapsulationHeader {
+    /// Parses the four bytes of an encapsulation header.
+    pub fn from_bytes(bytes: [u8; 4]) -> Result<Self> {
+        let kind =
+            EncapsulationKind::from_id([bytes[0], bytes[1]]).ok_or(Error::InvalidEncapsulation)?;
+        Ok(Self {
+            kind,
+            options: [bytes[2], bytes[3]],
+        })
+    }
+
+    /// Returns the four bytes of the header.
+    pub fn to_bytes(self) -> [u8; 4] {
+        let id = self.kind.id();
+        [id[0], id[1], self.options[0], self.options[1]]
+    }
+
+    /// Returns the representation identifier.
+    pub fn representation_id(self) -> [u8; 2] {
+        self.kind.id()
+    }
+
+    /// Returns the byte order of the encapsulated data.
+    pub fn endianness(self) -> Endianness {
+        self.kind.endianness()
+    }
+
+    /// Returns the number of padding bytes the options declare at the end of
+    /// the data, which XCDR2 writers add to reach a multiple of four bytes.
+    pub fn padding(self) -> usize {
+        usize::from(self.options[1] & OPTIONS_PADDING_MASK)
+    }
 }
 
 #[cfg(test)]
@@ -67,5 +376,93 @@ mod tests {
             ENCAPSULATION_HEADER_SIZE,
             (PlCdrLe::ID.len() + PlCdrLe::OPTION.len()) as u64
         );
+        assert_eq!(
+            ENCAPSULATION_HEADER_SIZE,
+            (Cdr2Be::ID.len() + Cdr2Be::OPTION.len()) as u64
+        );
+        assert_eq!(
+            ENCAPSULATION_HEADER_SIZE,
+            (Cdr2Le::ID.len() + Cdr2Le::OPTION.len()) as u64
+        );
+        assert_eq!(
+            ENCAPSULATION_HEADER_SIZE,
+            (DCdr2Be::ID.len() + DCdr2Be::OPTION.len()) as u64
+        );
+        assert_eq!(
+            ENCAPSULATION_HEADER_SIZE,
+            (DCdr2Le::ID.len() + DCdr2Le::OPTION.len()) as u64
+        );
+        assert_eq!(
+            ENCAPSULATION_HEADER_SIZE,
+            (PlCdr2Be::ID.len() + PlCdr2Be::OPTION.len()) as u64
+        );
+        assert_eq!(
+            ENCAPSULATION_HEADER_SIZE,
+            (PlCdr2Le::ID.len() + PlCdr2Le::OPTION.len()) as u64
+        );
+    }
+
+    #[test]
+    fn test_max_alignment() {
+        assert_eq!(CdrBe::VERSION.max_alignment(), 8);
+        assert_eq!(PlCdrLe::VERSION.max_alignment(), 8);
+        assert_eq!(Cdr2Be::VERSION.max_alignment(), 4);
+        assert_eq!(Cdr2Le::VERSION.max_alignment(), 4);
+    }
+
+    #[test]
+    fn test_struct_extensibility() {
+        let mutable = Extensibility::Mutable;
+        let appendable = Some(Extensibility::Appendable);
+        assert_eq!(mutable.of_struct(None, false), Extensibility::Mutable);
+        assert_eq!(mutable.of_struct(None, true), Extensibility::Final);
+        assert_eq!(
+            mutable.of_struct(Some(Extensibility::Final), false),
+            Extensibility::Final
+        );
+        assert_eq!(
+            Extensibility::Final.of_struct(appendable, true),
+            Extensibility::Appendable
+        );
+        assert_eq!(
+            Extensibility::Final.of_struct(Some(Extensibility::Mutable), true),
+            Extensibility::Mutable
+        );
+    }
+
+    #[test]
+    fn test_encapsulation_kind() {
+        fn check<C: Encapsulation>(kind: EncapsulationKind, endianness: Endianness) {
+            assert_eq!(EncapsulationKind::from_id(C::ID), Some(kind));
+            assert_eq!(kind.id(), C::ID);
+            assert_eq!(kind.endianness(), endianness);
+            assert_eq!(kind.version(), C::VERSION);
+            assert_eq!(kind.extensibility(), C::EXTENSIBILITY);
+        }
+
+        check::<CdrBe>(EncapsulationKind::CdrBe, Endianness::Big);
+        check::<CdrLe>(EncapsulationKind::CdrLe, Endianness::Little);
+        check::<PlCdrBe>(EncapsulationKind::PlCdrBe, Endianness::Big);
+        check::<PlCdrLe>(EncapsulationKind::PlCdrLe, Endianness::Little);
+        check::<Cdr2Be>(EncapsulationKind::Cdr2Be, Endianness::Big);
+        check::<Cdr2Le>(EncapsulationKind::Cdr2Le, Endianness::Little);
+        check::<DCdr2Be>(EncapsulationKind::DCdr2Be, Endianness::Big);
+        check::<DCdr2Le>(EncapsulationKind::DCdr2Le, Endianness::Little);
+        check::<PlCdr2Be>(EncapsulationKind::PlCdr2Be, Endianness::Big);
+        check::<PlCdr2Le>(EncapsulationKind::PlCdr2Le, Endianness::Little);
+        assert_eq!(EncapsulationKind::from_id([0, 4]), None);
+        assert_eq!(EncapsulationKind::from_id([1, 0]), None);
+    }
+
+    #[test]
+    fn test_encapsulation_header() {
+        let header = EncapsulationHeader::from_bytes([0, 3, 1, 2]).unwrap();
+        assert_eq!(header.kind, EncapsulationKind::PlCdrLe);
+        assert_eq!(header.options, [1, 2]);
+        assert_eq!(header.representation_id(), PlCdrLe::ID);
+        assert_eq!(header.endianness(), Endianness::Little);
+        assert_eq!(header.to_bytes(), [0, 3, 1, 2]);
+        assert_eq!(header.padding(), 2);
+        assert!(EncapsulationHeader::from_bytes([0, 0x0c, 0, 0]).is_err());
     }
 }
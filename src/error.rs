@@ -1,49 +1,243 @@
-use std::{fmt, io, str::Utf8Error};
+use alloc::{
+    boxed::Box,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{fmt, str::Utf8Error};
+#[cfg(feature = "std")]
+use std::io;
 
-/// Convenient wrapper around `std::Result`.
-pub type Result<T> = std::result::Result<T, Error>;
+/// Convenient wrapper around `core::result::Result`.
+pub type Result<T> = core::result::Result<T, Error>;
 
 /// The Error type.
-#[derive(Debug, thiserror::Error)]
+#[derive(Debug)]
 pub enum Error {
-    #[error("{0}")]
     Message(String),
-
-    #[error("{0}")]
-    Io(#[from] io::Error),
-
-    #[error("does not support the serde::Deserializer::deserialize_any method")]
+    #[cfg(feature = "std")]
+    Io(io::Error),
+    /// The buffer given to serialize into is too small, and `needed` bytes
+    /// would be enough.
+    BufferTooSmall {
+        needed: u64,
+    },
     DeserializeAnyNotSupported,
-
-    #[error("expected 0 or 1, found {0}")]
     InvalidBoolEncoding(u8),
-
-    #[error("expected char of width 1, found {0}")]
     InvalidChar(char),
-
-    #[error("char is not valid UTF-8")]
     InvalidCharEncoding,
-
-    #[error("encapsulation is not valid")]
     InvalidEncapsulation,
+    /// A GIOP message header or message is malformed, or holds something its
+    /// version does not allow.
+    InvalidMessage,
+    /// A padding byte at the given offset is not zero, which
+    /// `PaddingMode::Strict` rejects.
+    InvalidPadding(u64),
+    InvalidUtf8Encoding(Utf8Error),
+    /// A wide character or string is not valid UTF-16.
+    InvalidUtf16Encoding,
+    InvalidString(String),
+    /// A value extends beyond the length given in its header.
+    LengthExceeded,
+    NumberOutOfRange,
+    /// Compound values, options and newtypes are nested deeper than allowed.
+    RecursionLimitExceeded,
+    SequenceMustHaveLength,
+    SizeLimit,
+    /// The given number of bytes are left over after the value or the GIOP
+    /// message.
+    TrailingBytes(u64),
+    TypeNotSupported,
+    /// The input ended before the value was complete. Readers implementing
+    /// `std::io::Read` report it as `Error::Io` instead.
+    UnexpectedEof,
+    /// A member of a mutable struct with the given member id is marked as
+    /// must-understand but is not known to the type being deserialized.
+    UnknownMustUnderstand(u32),
+    /// A GIOP message has a version other than 1.0, 1.1 or 1.2.
+    UnsupportedVersion {
+        major: u8,
+        minor: u8,
+    },
+    /// The output has no room left for the value. Writers implementing
+    /// `std::io::Write` report it as `Error::Io` instead.
+    WriteZero,
+    /// An error returned by a deserializer created `with_error_positions`,
+    /// along with the offset from the start of the input of the value that
+    /// failed to decode and the path leading to it.
+    Positioned {
+        offset: u64,
+        path: Vec<PathSegment>,
+        error: Box<Error>,
+    },
+}
 
-    #[error("{0}")]
-    InvalidUtf8Encoding(#[source] Utf8Error),
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Message(msg) => f.write_str(msg),
+            #[cfg(feature = "std")]
+            Error::Io(err) => err.fmt(f),
+            Error::BufferTooSmall { needed } => {
+                write!(f, "buffer is too small, {} bytes are needed", needed)
+            }
+            Error::DeserializeAnyNotSupported => {
+                f.write_str("does not support the serde::Deserializer::deserialize_any method")
+            }
+            Error::InvalidBoolEncoding(v) => write!(f, "expected 0 or 1, found {}", v),
+            Error::InvalidChar(c) => write!(f, "expected char of width 1, found {}", c),
+            Error::InvalidCharEncoding => f.write_str("char is not valid UTF-8"),
+            Error::InvalidEncapsulation => f.write_str("encapsulation is not valid"),
+            Error::InvalidMessage => f.write_str("GIOP message is not valid"),
+            Error::InvalidPadding(offset) => write!(f, "padding at offset {} is not zero", offset),
+            Error::InvalidUtf8Encoding(err) => err.fmt(f),
+            Error::InvalidUtf16Encoding => f.write_str("wide string is not valid UTF-16"),
+            Error::InvalidString(s) => {
+                write!(f, "each character must have a length of 1, given \"{}\"", s)
+            }
+            Error::LengthExceeded => {
+                f.write_str("data extends beyond the length given in its header")
+            }
+            Error::NumberOutOfRange => f.write_str("sequence is too long"),
+            Error::RecursionLimitExceeded => f.write_str("values are nested too deeply"),
+            Error::SequenceMustHaveLength => {
+                f.write_str("sequences must have a knowable size ahead of time")
+            }
+            Error::SizeLimit => f.write_str("the size limit has been reached"),
+            Error::TrailingBytes(len) => write!(f, "{} bytes are left over after the value", len),
+            Error::TypeNotSupported => f.write_str("unsupported type"),
+            Error::UnexpectedEof => f.write_str("unexpected end of input"),
+            Error::UnknownMustUnderstand(id) => {
+                write!(f, "unknown member {} is marked as must-understand", id)
+            }
+            Error::UnsupportedVersion { major, minor } => {
+                write!(f, "GIOP version {}.{} is not supported", major, minor)
+            }
+            Error::WriteZero => f.write_str("failed to write the whole value"),
+            Error::Positioned {
+                offset,
+                path,
+                error,
+            } => {
+                write!(f, "{} at offset {}", error, offset)?;
+                display_path(f, path)
+            }
+        }
+    }
+}
 
-    #[error("each character must have a length of 1, given \"{0}\"")]
-    InvalidString(String),
+impl serde::ser::StdError for Error {
+    fn source(&self) -> Option<&(dyn serde::ser::StdError + 'static)> {
+        match self {
+            #[cfg(feature = "std")]
+            Error::Io(err) => Some(err),
+            #[cfg(feature = "std")]
+            Error::InvalidUtf8Encoding(err) => Some(err),
+            _ => None,
+        }
+    }
+}
 
-    #[error("sequence is too long")]
-    NumberOutOfRange,
+#[cfg(feature = "std")]
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
 
-    #[error("sequences must have a knowable size ahead of time")]
-    SequenceMustHaveLength,
+impl Error {
+    /// Returns whether the output has no room left for the value, whichever
+    /// kind of writer reports it.
+    pub(crate) fn is_write_zero(&self) -> bool {
+        match self {
+            Error::WriteZero => true,
+            #[cfg(feature = "std")]
+            Error::Io(err) => err.kind() == io::ErrorKind::WriteZero,
+            _ => false,
+        }
+    }
 
-    #[error("the size limit has been reached")]
-    SizeLimit,
+    /// Returns the byte offset from the start of the input of the value that
+    /// failed to decode, if known. Only deserializers created
+    /// `with_error_positions` report it.
+    pub fn offset(&self) -> Option<u64> {
+        match self {
+            Error::Positioned { offset, .. } => Some(*offset),
+            _ => None,
+        }
+    }
 
-    #[error("unsupported type")]
-    TypeNotSupported,
+    /// Returns the path from the top-level value to the one that failed to
+    /// decode.
+    pub fn path(&self) -> &[PathSegment] {
+        match self {
+            Error::Positioned { path, .. } => path,
+            _ => &[],
+        }
+    }
+
+    /// Returns the error without its offset and path.
+    pub fn inner(&self) -> &Error {
+        match self {
+            Error::Positioned { error, .. } => error,
+            error => error,
+        }
+    }
+
+    /// Attaches the offset of the value that failed to decode, unless already
+    /// known, and prepends `segment` to the path leading to it.
+    pub(crate) fn at(self, offset: u64, segment: Option<PathSegment>) -> Self {
+        match self {
+            Error::Positioned {
+                offset,
+                mut path,
+                error,
+            } => {
+                path.splice(..0, segment);
+                Error::Positioned {
+                    offset,
+                    path,
+                    error,
+                }
+            }
+            error => Error::Positioned {
+                offset,
+                path: segment.into_iter().collect(),
+                error: Box::new(error),
+            },
+        }
+    }
+}
+
+/// A step on the path from the top-level value to a nested one.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum PathSegment {
+    /// A field of a struct.
+    Field {
+        struct_name: &'static str,
+        field: &'static str,
+    },
+    /// An element of a sequence, an array or a tuple, or an entry of a map.
+    Index(usize),
+}
+
+impl fmt::Display for PathSegment {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PathSegment::Field { struct_name, field } => write!(f, "{}.{}", struct_name, field),
+            PathSegment::Index(index) => write!(f, "[{}]", index),
+        }
+    }
+}
+
+fn display_path(f: &mut fmt::Formatter, path: &[PathSegment]) -> fmt::Result {
+    for (i, segment) in path.iter().enumerate() {
+        match (segment, i) {
+            (_, 0) => write!(f, " in {}", segment)?,
+            (PathSegment::Field { .. }, _) => write!(f, ".{}", segment)?,
+            (PathSegment::Index(_), _) => write!(f, "{}", segment)?,
+        }
+    }
+    Ok(())
 }
 
 impl serde::de::Error for Error {
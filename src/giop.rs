@@ -0,0 +1,853 @@
+//! Messages of the General Inter-ORB Protocol (GIOP) versions 1.0 to 1.2.
+//!
+//! A message is a 12-byte header followed by a message header for its type
+//! and, for some types, a body holding the arguments or results of an
+//! operation. Alignment in the message header and body is relative to the
+//! start of the message. Wide characters and strings follow the conventions
+//! of GIOP 1.2 whatever the version.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use cdr::giop::{self, Message, RequestHeader, TargetAddress, Version, RESPONSE_EXPECTED};
+//! use cdr::BigEndian;
+//!
+//! let request = Message::Request(RequestHeader {
+//!     request_id: 1,
+//!     response_flags: RESPONSE_EXPECTED,
+//!     target: TargetAddress::KeyAddr(b"key".to_vec()),
+//!     operation: "add".to_string(),
+//!     service_context: Vec::new(),
+//!     requesting_principal: Vec::new(),
+//! });
+//! let encoded = giop::serialize_message::<_, BigEndian>(Version::V1_2, &request, &(1_i32, 2_i32))
+//!     .unwrap();
+//!
+//! let (header, message, body) = giop::deserialize_message(&encoded).unwrap();
+//! assert_eq!(header.version, Version::V1_2);
+//! assert_eq!(message, request);
+//! assert_eq!(body.deserialize::<(i32, i32)>().unwrap(), (1, 2));
+//! ```
+
+use alloc::{string::String, vec, vec::Vec};
+
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+use serde::{de, ser};
+
+use crate::{
+    de::{unexpected_eof, Deserializer, SliceReader},
+    encapsulated::{byte_order_flag, FLAG_BE},
+    encapsulation::Endianness,
+    error::{Error, Result},
+    ser::{CdrWrite, Serializer},
+    size::{calc_serialized_data_size_at, Infinite},
+    wide::WideEncoding,
+};
+
+/// The magic number starting every message.
+pub const MAGIC: [u8; 4] = *b"GIOP";
+
+/// The size of the header starting every message.
+pub const HEADER_SIZE: usize = 12;
+
+const FLAG_LITTLE_ENDIAN: u8 = 0x01;
+const FLAG_MORE_FRAGMENTS: u8 = 0x02;
+
+/// Response flags of a request for which no reply is expected.
+pub const RESPONSE_NONE: u8 = 0x00;
+
+/// Response flags of a request whose reply is sent once the server has
+/// received it, before the target is invoked.
+pub const RESPONSE_SYNC_WITH_SERVER: u8 = 0x01;
+
+/// Response flags of a request whose reply is sent once the target has been
+/// invoked, which is the only kind of reply before GIOP 1.2.
+pub const RESPONSE_EXPECTED: u8 = 0x03;
+
+/// A version of GIOP.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Version {
+    pub major: u8,
+    pub minor: u8,
+}
+
+impl Version {
+    pub const V1_0: Version = Version { major: 1, minor: 0 };
+    pub const V1_1: Version = Version { major: 1, minor: 1 };
+    pub const V1_2: Version = Version { major: 1, minor: 2 };
+
+    /// Fails with `Error::UnsupportedVersion` for a version other than 1.0,
+    /// 1.1 and 1.2.
+    fn check(self) -> Result<Self> {
+        if self.major == 1 && self.minor <= 2 {
+            Ok(self)
+        } else {
+            Err(Error::UnsupportedVersion {
+                major: self.major,
+                minor: self.minor,
+            })
+        }
+    }
+}
+
+/// The type of a message.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum MessageType {
+    Request = 0,
+    Reply = 1,
+    CancelRequest = 2,
+    LocateRequest = 3,
+    LocateReply = 4,
+    CloseConnection = 5,
+    MessageError = 6,
+    /// Since GIOP 1.1.
+    Fragment = 7,
+}
+
+impl MessageType {
+    /// Returns the message type with the given value, if any.
+    pub fn from_u8(v: u8) -> Option<Self> {
+        match v {
+            0 => Some(MessageType::Request),
+            1 => Some(MessageType::Reply),
+            2 => Some(MessageType::CancelRequest),
+            3 => Some(MessageType::LocateRequest),
+            4 => Some(MessageType::LocateReply),
+            5 => Some(MessageType::CloseConnection),
+            6 => Some(MessageType::MessageError),
+            7 => Some(MessageType::Fragment),
+            _ => None,
+        }
+    }
+}
+
+/// The header starting every message.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct MessageHeader {
+    pub version: Version,
+    /// The byte order of the rest of the message.
+    pub endianness: Endianness,
+    /// Whether fragments of the message follow, since GIOP 1.1.
+    pub more_fragments: bool,
+    pub message_type: MessageType,
+    /// The number of bytes following the header.
+    pub message_size: u32,
+}
+
+impl MessageHeader {
+    /// Parses the twelve bytes of a message header.
+    pub fn from_bytes(bytes: [u8; HEADER_SIZE]) -> Result<Self> {
+        if bytes[..4] != MAGIC {
+            return Err(Error::InvalidMessage);
+        }
+
+        let version = Version {
+            major: bytes[4],
+            minor: bytes[5],
+        }
+        .check()?;
+        let flags = bytes[6];
+        let known = match version {
+            Version::V1_0 => FLAG_LITTLE_ENDIAN,
+            _ => FLAG_LITTLE_ENDIAN | FLAG_MORE_FRAGMENTS,
+        };
+        if flags & !known != 0 {
+            return Err(Error::InvalidMessage);
+        }
+
+        let message_type = MessageType::from_u8(bytes[7])
+            .filter(|&t| t != MessageType::Fragment || version != Version::V1_0)
+            .ok_or(Error::InvalidMessage)?;
+        let (endianness, message_size) = if flags & FLAG_LITTLE_ENDIAN == 0 {
+            (Endianness::Big, BigEndian::read_u32(&bytes[8..]))
+        } else {
+            (Endianness::Little, LittleEndian::read_u32(&bytes[8..]))
+        };
+        Ok(Self {
+            version,
+            endianness,
+            more_fragments: flags & FLAG_MORE_FRAGMENTS != 0,
+            message_type,
+            message_size,
+        })
+    }
+
+    /// Returns the twelve bytes of the header.
+    pub fn to_bytes(self) -> [u8; HEADER_SIZE] {
+        let mut bytes = [0; HEADER_SIZE];
+        bytes[..4].copy_from_slice(&MAGIC);
+        bytes[4] = self.version.major;
+        bytes[5] = self.version.minor;
+        if self.more_fragments {
+            bytes[6] |= FLAG_MORE_FRAGMENTS;
+        }
+        bytes[7] = self.message_type as u8;
+        match self.endianness {
+            Endianness::Big => BigEndian::write_u32(&mut bytes[8..], self.message_size),
+            Endianness::Little => {
+                bytes[6] |= FLAG_LITTLE_ENDIAN;
+                LittleEndian::write_u32(&mut bytes[8..], self.message_size);
+            }
+        }
+        bytes
+    }
+}
+
+/// Implements `Serialize` and `Deserialize` for a struct by way of a tuple
+/// of its fields.
+macro_rules! impl_serde_as_tuple {
+    ($name:ident { $($field:ident: $ty:ty),+ }) => {
+        impl ser::Serialize for $name {
+            fn serialize<S>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error>
+            where
+                S: ser::Serializer,
+            {
+                ser::Serialize::serialize(&($(&self.$field,)+), serializer)
+            }
+        }
+
+        impl<'de> de::Deserialize<'de> for $name {
+            fn deserialize<D>(deserializer: D) -> core::result::Result<Self, D::Error>
+            where
+                D: de::Deserializer<'de>,
+            {
+                let ($($field,)+): ($($ty,)+) = de::Deserialize::deserialize(deserializer)?;
+                Ok($name { $($field),+ })
+            }
+        }
+    };
+}
+
+/// An `IOP::ServiceContext`, data attached to a request or a reply by a
+/// service of the ORB.
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct ServiceContext {
+    pub context_id: u32,
+    pub context_data: Vec<u8>,
+}
+
+impl_serde_as_tuple!(ServiceContext {
+    context_id: u32,
+    context_data: Vec<u8>
+});
+
+/// An `IOP::TaggedProfile`, one of the ways to reach an object.
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct TaggedProfile {
+    pub tag: u32,
+    pub profile_data: Vec<u8>,
+}
+
+impl_serde_as_tuple!(TaggedProfile {
+    tag: u32,
+    profile_data: Vec<u8>
+});
+
+/// An `IOP::IOR`, an interoperable object reference, as found for instance
+/// in the body of a reply forwarding to another location.
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct Ior {
+    pub type_id: String,
+    pub profiles: Vec<TaggedProfile>,
+}
+
+impl_serde_as_tuple!(Ior {
+    type_id: String,
+    profiles: Vec<TaggedProfile>
+});
+
+/// The object targeted by a request.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum TargetAddress {
+    /// The object key, which is the only addressing before GIOP 1.2.
+    KeyAddr(Vec<u8>),
+    /// A profile of the object reference.
+    ProfileAddr(TaggedProfile),
+    /// The object reference, with the index of the profile the client used.
+    ReferenceAddr {
+        selected_profile_index: u32,
+        ior: Ior,
+    },
+}
+
+impl TargetAddress {
+    /// Returns the object key that addresses the target before GIOP 1.2.
+    fn object_key(&self) -> Result<&Vec<u8>> {
+        match self {
+            TargetAddress::KeyAddr(key) => Ok(key),
+            _ => Err(Error::InvalidMessage),
+        }
+    }
+}
+
+/// The message header of a `Request`.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct RequestHeader {
+    pub request_id: u32,
+    /// One of `RESPONSE_NONE`, `RESPONSE_SYNC_WITH_SERVER` and
+    /// `RESPONSE_EXPECTED`. Before GIOP 1.2 only whether a reply is expected
+    /// is written, which reads back as `RESPONSE_NONE` or `RESPONSE_EXPECTED`.
+    pub response_flags: u8,
+    pub target: TargetAddress,
+    pub operation: String,
+    pub service_context: Vec<ServiceContext>,
+    /// Only written before GIOP 1.2.
+    pub requesting_principal: Vec<u8>,
+}
+
+/// The status of a `Reply`, which decides what its body holds.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum ReplyStatus {
+    NoException = 0,
+    UserException = 1,
+    SystemException = 2,
+    LocationForward = 3,
+    /// Since GIOP 1.2.
+    LocationForwardPerm = 4,
+    /// Since GIOP 1.2.
+    NeedsAddressingMode = 5,
+}
+
+impl ReplyStatus {
+    fn from_u32(v: u32, version: Version) -> Result<Self> {
+        match (v, version >= Version::V1_2) {
+            (0, _) => Ok(ReplyStatus::NoException),
+            (1, _) => Ok(ReplyStatus::UserException),
+            (2, _) => Ok(ReplyStatus::SystemException),
+            (3, _) => Ok(ReplyStatus::LocationForward),
+            (4, true) => Ok(ReplyStatus::LocationForwardPerm),
+            (5, true) => Ok(ReplyStatus::NeedsAddressingMode),
+            _ => Err(Error::InvalidMessage),
+        }
+    }
+}
+
+/// The message header of a `Reply`.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct ReplyHeader {
+    pub request_id: u32,
+    pub reply_status: ReplyStatus,
+    pub service_context: Vec<ServiceContext>,
+}
+
+/// The message header of a `CancelRequest`.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct CancelRequestHeader {
+    pub request_id: u32,
+}
+
+/// The message header of a `LocateRequest`.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct LocateRequestHeader {
+    pub request_id: u32,
+    pub target: TargetAddress,
+}
+
+/// The status of a `LocateReply`, which decides what its body holds.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum LocateStatus {
+    UnknownObject = 0,
+    ObjectHere = 1,
+    ObjectForward = 2,
+    /// Since GIOP 1.2.
+    ObjectForwardPerm = 3,
+    /// Since GIOP 1.2.
+    LocSystemException = 4,
+    /// Since GIOP 1.2.
+    LocNeedsAddressingMode = 5,
+}
+
+impl LocateStatus {
+    fn from_u32(v: u32, version: Version) -> Result<Self> {
+        match (v, version >= Version::V1_2) {
+            (0, _) => Ok(LocateStatus::UnknownObject),
+            (1, _) => Ok(LocateStatus::ObjectHere),
+            (2, _) => Ok(LocateStatus::ObjectForward),
+            (3, true) => Ok(LocateStatus::ObjectForwardPerm),
+            (4, true) => Ok(LocateStatus::LocSystemException),
+            (5, true) => Ok(LocateStatus::LocNeedsAddressingMode),
+            _ => Err(Error::InvalidMessage),
+        }
+    }
+}
+
+/// The message header of a `LocateReply`.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct LocateReplyHeader {
+    pub request_id: u32,
+    pub locate_status: LocateStatus,
+}
+
+/// The message header of a `Fragment` in GIOP 1.2.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct FragmentHeader {
+    pub request_id: u32,
+}
+
+/// A message without its body.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum Message {
+    Request(RequestHeader),
+    Reply(ReplyHeader),
+    CancelRequest(CancelRequestHeader),
+    LocateRequest(LocateRequestHeader),
+    LocateReply(LocateReplyHeader),
+    CloseConnection,
+    MessageError,
+    /// The continuation of the previous message, whose request it names in
+    /// GIOP 1.2 only.
+    Fragment(Option<FragmentHeader>),
+}
+
+impl Message {
+    /// Returns the type of the message.
+    pub fn message_type(&self) -> MessageType {
+        match self {
+            Message::Request(_) => MessageType::Request,
+            Message::Reply(_) => MessageType::Reply,
+            Message::CancelRequest(_) => MessageType::CancelRequest,
+            Message::LocateRequest(_) => MessageType::LocateRequest,
+            Message::LocateReply(_) => MessageType::LocateReply,
+            Message::CloseConnection => MessageType::CloseConnection,
+            Message::MessageError => MessageType::MessageError,
+            Message::Fragment(_) => MessageType::Fragment,
+        }
+    }
+
+    /// Returns whether a body starts on an 8-byte boundary after the message
+    /// header, as for requests and replies since GIOP 1.2.
+    fn aligns_body(&self, version: Version) -> bool {
+        version >= Version::V1_2
+            && matches!(
+                self,
+                Message::Request(_) | Message::Reply(_) | Message::LocateReply(_)
+            )
+    }
+}
+
+/// Writes the message header of `message` in `version`.
+fn write_message<W, E>(
+    serializer: &mut Serializer<W, E>,
+    version: Version,
+    message: &Message,
+) -> Result<()>
+where
+    W: CdrWrite,
+    E: ByteOrder,
+{
+    let v1_2 = version >= Version::V1_2;
+    match message {
+        Message::Request(h) if v1_2 => {
+            ser::Serialize::serialize(
+                &(h.request_id, h.response_flags, [0_u8; 3]),
+                &mut *serializer,
+            )?;
+            write_target(serializer, &h.target)?;
+            ser::Serialize::serialize(&(&h.operation, &h.service_context), serializer)
+        }
+        Message::Request(h) => {
+            let response_expected = h.response_flags & RESPONSE_SYNC_WITH_SERVER != 0;
+            ser::Serialize::serialize(
+                &(&h.service_context, h.request_id, response_expected),
+                &mut *serializer,
+            )?;
+            if version == Version::V1_1 {
+                ser::Serialize::serialize(&[0_u8; 3], &mut *serializer)?;
+            }
+            ser::Serialize::serialize(
+                &(
+                    h.target.object_key()?,
+                    &h.operation,
+                    &h.requesting_principal,
+                ),
+                serializer,
+            )
+        }
+        Message::Reply(h) => {
+            if h.reply_status as u32 > ReplyStatus::LocationForward as u32 && !v1_2 {
+                return Err(Error::InvalidMessage);
+            }
+            let status = h.reply_status as u32;
+            if v1_2 {
+                ser::Serialize::serialize(&(h.request_id, status, &h.service_context), serializer)
+            } else {
+                ser::Serialize::serialize(&(&h.service_context, h.request_id, status), serializer)
+            }
+        }
+        Message::CancelRequest(h) => ser::Serialize::serialize(&h.request_id, serializer),
+        Message::LocateRequest(h) => {
+            ser::Serialize::serialize(&h.request_id, &mut *serializer)?;
+            if v1_2 {
+                write_target(serializer, &h.target)
+            } else {
+                ser::Serialize::serialize(h.target.object_key()?, serializer)
+            }
+        }
+        Message::LocateReply(h) => {
+            if h.locate_status as u32 > LocateStatus::ObjectForward as u32 && !v1_2 {
+                return Err(Error::InvalidMessage);
+            }
+            ser::Serialize::serialize(&(h.request_id, h.locate_status as u32), serializer)
+        }
+        Message::CloseConnection | Message::MessageError => Ok(()),
+        Message::Fragment(h) => match (h, version) {
+            (_, Version::V1_0) => Err(Error::InvalidMessage),
+            (None, Version::V1_1) => Ok(()),
+            (Some(h), _) if v1_2 => ser::Serialize::serialize(&h.request_id, serializer),
+            _ => Err(Error::InvalidMessage),
+        },
+    }
+}
+
+/// Writes a `GIOP::TargetAddress`, a union discriminated by a `short`.
+fn write_target<W, E>(serializer: &mut Serializer<W, E>, target: &TargetAddress) -> Result<()>
+where
+    W: CdrWrite,
+    E: ByteOrder,
+{
+    match target {
+        TargetAddress::KeyAddr(key) => ser::Serialize::serialize(&(0_i16, key), serializer),
+        TargetAddress::ProfileAddr(profile) => {
+            ser::Serialize::serialize(&(1_i16, profile), serializer)
+        }
+        TargetAddress::ReferenceAddr {
+            selected_profile_index,
+            ior,
+        } => ser::Serialize::serialize(&(2_i16, selected_profile_index, ior), serializer),
+    }
+}
+
+/// Reads the message header of a message of `message_type` in `version`.
+fn read_message<'de, E>(
+    deserializer: &mut Deserializer<SliceReader<'de>, Infinite, E>,
+    version: Version,
+    message_type: MessageType,
+) -> Result<Message>
+where
+    E: ByteOrder,
+{
+    let v1_2 = version >= Version::V1_2;
+    let message = match message_type {
+        MessageType::Request if v1_2 => {
+            let (request_id, response_flags, _): (u32, u8, [u8; 3]) =
+                de::Deserialize::deserialize(&mut *deserializer)?;
+            let target = read_target(deserializer)?;
+            let (operation, service_context) = de::Deserialize::deserialize(deserializer)?;
+            Message::Request(RequestHeader {
+                request_id,
+                response_flags,
+                target,
+                operation,
+                service_context,
+                requesting_principal: Vec::new(),
+            })
+        }
+        MessageType::Request => {
+            let (service_context, request_id, response_expected): (_, _, bool) =
+                de::Deserialize::deserialize(&mut *deserializer)?;
+            if version == Version::V1_1 {
+                let _: [u8; 3] = de::Deserialize::deserialize(&mut *deserializer)?;
+            }
+            let (object_key, operation, requesting_principal) =
+                de::Deserialize::deserialize(deserializer)?;
+            Message::Request(RequestHeader {
+                request_id,
+                response_flags: if response_expected {
+                    RESPONSE_EXPECTED
+                } else {
+                    RESPONSE_NONE
+                },
+                target: TargetAddress::KeyAddr(object_key),
+                operation,
+                service_context,
+                requesting_principal,
+            })
+        }
+        MessageType::Reply => {
+            let (service_context, request_id, status) = if v1_2 {
+                let (request_id, status, service_context) =
+                    de::Deserialize::deserialize(deserializer)?;
+                (service_context, request_id, status)
+            } else {
+                de::Deserialize::deserialize(deserializer)?
+            };
+            Message::Reply(ReplyHeader {
+                request_id,
+                reply_status: ReplyStatus::from_u32(status, version)?,
+                service_context,
+            })
+        }
+        MessageType::CancelRequest => Message::CancelRequest(CancelRequestHeader {
+            request_id: de::Deserialize::deserialize(deserializer)?,
+        }),
+        MessageType::LocateRequest => {
+            let request_id = de::Deserialize::deserialize(&mut *deserializer)?;
+            let target = if v1_2 {
+                read_target(deserializer)?
+            } else {
+                TargetAddress::KeyAddr(de::Deserialize::deserialize(deserializer)?)
+            };
+            Message::LocateRequest(LocateRequestHeader { request_id, target })
+        }
+        MessageType::LocateReply => {
+            let (request_id, status) = de::Deserialize::deserialize(deserializer)?;
+            Message::LocateReply(LocateReplyHeader {
+                request_id,
+                locate_status: LocateStatus::from_u32(status, version)?,
+            })
+        }
+        MessageType::CloseConnection => Message::CloseConnection,
+        MessageType::MessageError => Message::MessageError,
+        MessageType::Fragment if v1_2 => Message::Fragment(Some(FragmentHeader {
+            request_id: de::Deserialize::deserialize(deserializer)?,
+        })),
+        MessageType::Fragment => Message::Fragment(None),
+    };
+    Ok(message)
+}
+
+/// Reads a `GIOP::TargetAddress`.
+fn read_target<'de, E>(
+    deserializer: &mut Deserializer<SliceReader<'de>, Infinite, E>,
+) -> Result<TargetAddress>
+where
+    E: ByteOrder,
+{
+    let disc: i16 = de::Deserialize::deserialize(&mut *deserializer)?;
+    match disc {
+        0 => de::Deserialize::deserialize(deserializer).map(TargetAddress::KeyAddr),
+        1 => de::Deserialize::deserialize(deserializer).map(TargetAddress::ProfileAddr),
+        2 => {
+            let (selected_profile_index, ior) = de::Deserialize::deserialize(deserializer)?;
+            Ok(TargetAddress::ReferenceAddr {
+                selected_profile_index,
+                ior,
+            })
+        }
+        _ => Err(Error::InvalidMessage),
+    }
+}
+
+/// Serializes a message of `version` in the byte order `E`, with `body`
+/// holding the arguments or results of an operation, or `()` for none.
+pub fn serialize_message<T, E>(version: Version, message: &Message, body: &T) -> Result<Vec<u8>>
+where
+    T: ser::Serialize + ?Sized,
+    E: ByteOrder,
+{
+    serialize_message_with::<E, _>(version, message, false, |buf, pos| {
+        let mut serializer =
+            Serializer::<_, E>::new_at(buf, pos).with_wide_encoding(WideEncoding::Giop);
+        ser::Serialize::serialize(body, &mut serializer)
+    })
+}
+
+/// Serializes a message of `version` in the byte order `E` followed by `body`
+/// as is, as for a message split into fragments. The body must have been
+/// encoded for the position where it starts in the unfragmented message.
+pub fn serialize_message_bytes<E>(
+    version: Version,
+    message: &Message,
+    body: &[u8],
+    more_fragments: bool,
+) -> Result<Vec<u8>>
+where
+    E: ByteOrder,
+{
+    serialize_message_with::<E, _>(version, message, more_fragments, |buf, _| {
+        buf.extend_from_slice(body);
+        Ok(())
+    })
+}
+
+/// Returns the size of `body` encoded to start `pos` bytes into a message, as
+/// for a body to be split into fragments and sent with
+/// `serialize_message_bytes`.
+pub fn calc_body_size<T>(body: &T, pos: u64) -> Result<u64>
+where
+    T: ser::Serialize + ?Sized,
+{
+    calc_serialized_data_size_at(body, pos, WideEncoding::Giop)
+}
+
+/// Serializes a message whose body `write_body` appends to a buffer, given the
+/// position at which the body starts.
+fn serialize_message_with<E, F>(
+    version: Version,
+    message: &Message,
+    more_fragments: bool,
+    write_body: F,
+) -> Result<Vec<u8>>
+where
+    E: ByteOrder,
+    F: FnOnce(&mut Vec<u8>, u64) -> Result<()>,
+{
+    version.check()?;
+    if more_fragments && version == Version::V1_0 {
+        return Err(Error::InvalidMessage);
+    }
+
+    let mut buf = vec![0; HEADER_SIZE];
+    let mut serializer = Serializer::<_, E>::new_at(&mut buf, HEADER_SIZE as u64)
+        .with_wide_encoding(WideEncoding::Giop);
+    write_message(&mut serializer, version, message)?;
+
+    let padding = if message.aligns_body(version) {
+        (8 - buf.len() % 8) % 8
+    } else {
+        0
+    };
+    let mut body = Vec::new();
+    write_body(&mut body, (buf.len() + padding) as u64)?;
+    if !body.is_empty() {
+        buf.resize(buf.len() + padding, 0);
+        buf.extend_from_slice(&body);
+    }
+
+    let message_size =
+        u32::try_from(buf.len() - HEADER_SIZE).map_err(|_| Error::NumberOutOfRange)?;
+    let header = MessageHeader {
+        version,
+        endianness: match byte_order_flag::<E>() {
+            FLAG_BE => Endianness::Big,
+            _ => Endianness::Little,
+        },
+        more_fragments,
+        message_type: message.message_type(),
+        message_size,
+    };
+    buf[..HEADER_SIZE].copy_from_slice(&header.to_bytes());
+    Ok(buf)
+}
+
+/// Deserializes a whole message, leaving its body to be deserialized once its
+/// type is known.
+pub fn deserialize_message(bytes: &[u8]) -> Result<(MessageHeader, Message, Body<'_>)> {
+    if bytes.len() < HEADER_SIZE {
+        return Err(unexpected_eof());
+    }
+
+    let mut header = [0; HEADER_SIZE];
+    header.copy_from_slice(&bytes[..HEADER_SIZE]);
+    let header = MessageHeader::from_bytes(header)?;
+    let end = HEADER_SIZE + header.message_size as usize;
+    if bytes.len() < end {
+        return Err(unexpected_eof());
+    }
+    if bytes.len() > end {
+        return Err(Error::TrailingBytes((bytes.len() - end) as u64));
+    }
+
+    let rest = &bytes[HEADER_SIZE..];
+    let (message, read) = match header.endianness {
+        Endianness::Big => read_message_from::<BigEndian>(rest, &header)?,
+        Endianness::Little => read_message_from::<LittleEndian>(rest, &header)?,
+    };
+    let mut pos = HEADER_SIZE + read;
+    if message.aligns_body(header.version) && pos < end {
+        pos += (8 - pos % 8) % 8;
+        if pos > end {
+            return Err(Error::InvalidMessage);
+        }
+    }
+    let body = Body {
+        bytes: &bytes[pos..],
+        pos: pos as u64,
+        endianness: header.endianness,
+    };
+    Ok((header, message, body))
+}
+
+/// Reads the message header following `header` from `bytes`, returning it
+/// with the number of bytes read.
+fn read_message_from<E>(bytes: &[u8], header: &MessageHeader) -> Result<(Message, usize)>
+where
+    E: ByteOrder,
+{
+    let mut deserializer =
+        Deserializer::<_, _, E>::from_slice_at(bytes, Infinite, HEADER_SIZE as u64)
+            .with_wide_encoding(WideEncoding::Giop);
+    let message = read_message(&mut deserializer, header.version, header.message_type)?;
+    Ok((message, deserializer.offset() as usize))
+}
+
+/// The body of a message read by `deserialize_message`.
+#[derive(Clone, Copy, Debug)]
+pub struct Body<'a> {
+    bytes: &'a [u8],
+    pos: u64,
+    endianness: Endianness,
+}
+
+impl<'a> Body<'a> {
+    /// Returns the bytes of the body.
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    /// Returns the position of the body from the start of the message, which
+    /// alignment is relative to.
+    pub fn position(&self) -> u64 {
+        self.pos
+    }
+
+    /// Deserializes the body, failing if any bytes are left over.
+    pub fn deserialize<T>(&self) -> Result<T>
+    where
+        T: de::Deserialize<'a>,
+    {
+        match self.endianness {
+            Endianness::Big => self.deserialize_as::<T, BigEndian>(),
+            Endianness::Little => self.deserialize_as::<T, LittleEndian>(),
+        }
+    }
+
+    fn deserialize_as<T, E>(&self) -> Result<T>
+    where
+        T: de::Deserialize<'a>,
+        E: ByteOrder,
+    {
+        let mut deserializer =
+            Deserializer::<_, _, E>::from_slice_at(self.bytes, Infinite, self.pos)
+                .with_wide_encoding(WideEncoding::Giop);
+        let value = deserializer.deserialize_value()?;
+        deserializer.end()?;
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_message_header() {
+        let header = MessageHeader {
+            version: Version::V1_2,
+            endianness: Endianness::Little,
+            more_fragments: true,
+            message_type: MessageType::Fragment,
+            message_size: 0x0102,
+        };
+        let bytes = header.to_bytes();
+        assert_eq!(bytes, *b"GIOP\x01\x02\x03\x07\x02\x01\x00\x00");
+        assert_eq!(MessageHeader::from_bytes(bytes).unwrap(), header);
+
+        let mut bytes = bytes;
+        bytes[5] = 0;
+        assert!(matches!(
+            MessageHeader::from_bytes(bytes),
+            Err(Error::InvalidMessage)
+        ));
+        bytes[5] = 3;
+        assert!(matches!(
+            MessageHeader::from_bytes(bytes),
+            Err(Error::UnsupportedVersion { major: 1, minor: 3 })
+        ));
+        bytes[0] = b'X';
+        assert!(matches!(
+            MessageHeader::from_bytes(bytes),
+            Err(Error::InvalidMessage)
+        ));
+    }
+}
@@ -27,32 +27,90 @@
 //!
 //! assert!(triangle == decoded);
 //! ```
+//!
+//! # XTypes
+//!
+//! The XCDR2 encapsulations and the mutable ones need type information that
+//! serde does not convey. It is given by wrapping values in the types below,
+//! an explicit opt-in that other formats see as a newtype around the value:
+//!
+//! - `Final`, `Appendable` and `Mutable` give a struct that extensibility kind
+//!   wherever it appears. Otherwise the outermost struct has the kind of the
+//!   encapsulation and nested structs are final.
+//! - `Delimited` has a sequence, an array or a map of elements that are not
+//!   primitive preceded by the DHEADER of XCDR2.
+//! - `Key` sets the must-understand flag of a member of a mutable struct.
+//!
+//! Members of mutable structs are numbered in declaration order from zero. A
+//! field renamed with `#[serde(rename = "name@id")]`, with `id` in decimal or
+//! `0x`-prefixed hexadecimal, takes `id` as its member id instead. This is an
+//! opt-in as well, but other formats see the renamed field as is.
+//!
+//! # Options
+//!
+//! An `Option` member of a final or appendable struct is an optional member.
+//! When absent, XCDR1 writes a parameter header of zero length and XCDR2 a
+//! `false` presence flag. Absent members of mutable structs are left out. An
+//! `Option` anywhere else, at the top level, in a collection, a tuple or an
+//! enum variant, is preceded by a boolean presence flag in both versions.
+//!
+//! As a zero length marks an absent member, XCDR1 cannot write a present
+//! optional member of a final or appendable struct whose value has no size,
+//! such as `Some(())`, and fails with `Error::TypeNotSupported`.
 
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 #![deny(warnings, clippy::all)]
 
+extern crate alloc;
+
+use alloc::vec::Vec;
+
+use byteorder::ByteOrder;
 pub use byteorder::{BigEndian, LittleEndian};
 
 pub mod de;
 #[doc(inline)]
-pub use crate::de::Deserializer;
+pub use crate::de::{Deserializer, PaddingMode};
+
+mod encapsulated;
+pub use crate::encapsulated::Encapsulated;
 
 mod encapsulation;
-pub use crate::encapsulation::{CdrBe, CdrLe, Encapsulation, PlCdrBe, PlCdrLe};
+use crate::encapsulation::OPTIONS_PADDING_MASK;
+pub use crate::encapsulation::{
+    Cdr2Be, Cdr2Le, CdrBe, CdrLe, CdrVersion, DCdr2Be, DCdr2Le, Encapsulation, EncapsulationHeader,
+    EncapsulationKind, Endianness, Extensibility, PlCdr2Be, PlCdr2Le, PlCdrBe, PlCdrLe,
+};
 
 mod error;
-pub use crate::error::{Error, Result};
+pub use crate::error::{Error, PathSegment, Result};
+
+pub mod giop;
+
+mod parameter;
+
+mod primitive;
+pub use crate::primitive::{Primitive, PrimitiveArray, Primitives};
 
 pub mod ser;
 #[doc(inline)]
 pub use crate::ser::Serializer;
 
 pub mod size;
-use std::io::{Read, Write};
 
 #[doc(inline)]
 pub use crate::size::{Bounded, Infinite, SizeLimit};
 
+mod wide;
+pub use crate::wide::{WChar, WString, WideEncoding};
+
+mod xtypes;
+pub use crate::xtypes::{Appendable, Delimited, Final, Key, Mutable};
+
 /// Returns the size that an object would be if serialized with a encapsulation.
+///
+/// The size is that of plain CDR, whose structs are final. Use
+/// `calc_serialized_size_with` for the other encapsulations.
 pub fn calc_serialized_size<T>(value: &T) -> u64
 where
     T: serde::Serialize + ?Sized,
@@ -62,6 +120,9 @@ where
 
 /// Given a maximum size limit, check how large an object would be if it were
 /// to be serialized with a encapsulation.
+///
+/// The size is that of plain CDR, whose structs are final. Use
+/// `calc_serialized_size_bounded_with` for the other encapsulations.
 pub fn calc_serialized_size_bounded<T>(value: &T, max: u64) -> Result<u64>
 where
     T: serde::Serialize + ?Sized,
@@ -76,76 +137,543 @@ where
     }
 }
 
+/// Returns the size that an object would be if serialized with the
+/// encapsulation given at runtime, including the padding ending XCDR2 data.
+pub fn calc_serialized_size_with<T, H>(value: &T, header: H) -> Result<u64>
+where
+    T: serde::Serialize + ?Sized,
+    H: Into<EncapsulationHeader>,
+{
+    calc_encapsulated_size(value, header.into().into())
+}
+
+/// Given a maximum size limit, check how large an object would be if it were
+/// to be serialized with the encapsulation given at runtime.
+pub fn calc_serialized_size_bounded_with<T, H>(value: &T, header: H, max: u64) -> Result<u64>
+where
+    T: serde::Serialize + ?Sized,
+    H: Into<EncapsulationHeader>,
+{
+    calc_encapsulated_size_bounded(value, max, header.into().into())
+}
+
 /// Serializes a serializable object into a `Vec` of bytes with the
 /// encapsulation.
+///
+/// See [Options](crate#options) for the optional members that cannot be
+/// written.
 pub fn serialize<T, S, C>(value: &T, size_limit: S) -> Result<Vec<u8>>
 where
     T: serde::Serialize + ?Sized,
     S: SizeLimit,
     C: Encapsulation,
 {
-    let mut writer = match size_limit.limit() {
-        Some(limit) => {
-            let actual_size = calc_serialized_size_bounded(value, limit)?;
-            Vec::with_capacity(actual_size as usize)
+    serialize_scheme::<_, _, C::E>(value, size_limit, Scheme::of::<C>())
+}
+
+/// Serializes a serializable object into a `Vec` of bytes with the
+/// encapsulation given at runtime.
+///
+/// `header` is either an `EncapsulationKind`, written with no options, or an
+/// `EncapsulationHeader`, such as one returned by `deserialize_with_header`,
+/// whose options are written as given apart from the bits declaring the end
+/// padding. The other functions taking the encapsulation at runtime do the
+/// same.
+///
+/// See [Options](crate#options) for the optional members that cannot be
+/// written.
+pub fn serialize_with<T, H, S>(value: &T, header: H, size_limit: S) -> Result<Vec<u8>>
+where
+    T: serde::Serialize + ?Sized,
+    H: Into<EncapsulationHeader>,
+    S: SizeLimit,
+{
+    let header = header.into();
+    match header.endianness() {
+        Endianness::Big => serialize_scheme::<_, _, BigEndian>(value, size_limit, header.into()),
+        Endianness::Little => {
+            serialize_scheme::<_, _, LittleEndian>(value, size_limit, header.into())
         }
-        None => {
-            let size = calc_serialized_size(value) as usize;
-            Vec::with_capacity(size)
+    }
+}
+
+/// Serializes an object directly into a `CdrWrite` with the encapsulation.
+///
+/// Under a bounded size limit, and for XCDR2 whose header declares the
+/// padding ending the data, the value is serialized into a buffer before
+/// being written, so that nothing is written if it exceeds the limit.
+pub fn serialize_into<W, T, S, C>(writer: W, value: &T, size_limit: S) -> Result<()>
+where
+    W: ser::CdrWrite,
+    T: serde::ser::Serialize + ?Sized,
+    S: SizeLimit,
+    C: Encapsulation,
+{
+    serialize_scheme_into::<_, _, _, C::E>(writer, value, size_limit, Scheme::of::<C>())
+}
+
+/// Serializes an object with the encapsulation into `buf` in place of its
+/// contents, reusing its capacity.
+pub fn serialize_into_vec<T, S, C>(buf: &mut Vec<u8>, value: &T, size_limit: S) -> Result<()>
+where
+    T: serde::ser::Serialize + ?Sized,
+    S: SizeLimit,
+    C: Encapsulation,
+{
+    serialize_scheme_into_vec::<_, _, C::E>(buf, value, size_limit, Scheme::of::<C>())
+}
+
+/// Serializes an object with the encapsulation into `buf`, returning the
+/// number of bytes written.
+pub fn serialize_into_slice<T, S, C>(buf: &mut [u8], value: &T, size_limit: S) -> Result<usize>
+where
+    T: serde::ser::Serialize + ?Sized,
+    S: SizeLimit,
+    C: Encapsulation,
+{
+    serialize_scheme_into_slice::<_, _, C::E>(buf, value, size_limit, Scheme::of::<C>())
+}
+
+/// Serializes an object directly into a `CdrWrite` with the encapsulation given
+/// at runtime.
+pub fn serialize_into_with<W, T, H, S>(writer: W, value: &T, header: H, size_limit: S) -> Result<()>
+where
+    W: ser::CdrWrite,
+    T: serde::ser::Serialize + ?Sized,
+    H: Into<EncapsulationHeader>,
+    S: SizeLimit,
+{
+    let header = header.into();
+    match header.endianness() {
+        Endianness::Big => {
+            serialize_scheme_into::<_, _, _, BigEndian>(writer, value, size_limit, header.into())
         }
-    };
+        Endianness::Little => {
+            serialize_scheme_into::<_, _, _, LittleEndian>(writer, value, size_limit, header.into())
+        }
+    }
+}
+
+/// Serializes an object with the encapsulation given at runtime into `buf` in
+/// place of its contents, reusing its capacity.
+pub fn serialize_into_vec_with<T, H, S>(
+    buf: &mut Vec<u8>,
+    value: &T,
+    header: H,
+    size_limit: S,
+) -> Result<()>
+where
+    T: serde::ser::Serialize + ?Sized,
+    H: Into<EncapsulationHeader>,
+    S: SizeLimit,
+{
+    let header = header.into();
+    match header.endianness() {
+        Endianness::Big => {
+            serialize_scheme_into_vec::<_, _, BigEndian>(buf, value, size_limit, header.into())
+        }
+        Endianness::Little => {
+            serialize_scheme_into_vec::<_, _, LittleEndian>(buf, value, size_limit, header.into())
+        }
+    }
+}
+
+/// Serializes an object with the encapsulation given at runtime into `buf`,
+/// returning the number of bytes written.
+pub fn serialize_into_slice_with<T, H, S>(
+    buf: &mut [u8],
+    value: &T,
+    header: H,
+    size_limit: S,
+) -> Result<usize>
+where
+    T: serde::ser::Serialize + ?Sized,
+    H: Into<EncapsulationHeader>,
+    S: SizeLimit,
+{
+    let header = header.into();
+    match header.endianness() {
+        Endianness::Big => {
+            serialize_scheme_into_slice::<_, _, BigEndian>(buf, value, size_limit, header.into())
+        }
+        Endianness::Little => {
+            serialize_scheme_into_slice::<_, _, LittleEndian>(buf, value, size_limit, header.into())
+        }
+    }
+}
+
+/// The parts of an encapsulation that do not depend on the byte order.
+#[derive(Clone, Copy)]
+struct Scheme {
+    id: [u8; 2],
+    option: [u8; 2],
+    version: CdrVersion,
+    extensibility: Extensibility,
+}
+
+impl Scheme {
+    fn of<C>() -> Self
+    where
+        C: Encapsulation,
+    {
+        Self {
+            id: C::ID,
+            option: C::OPTION,
+            version: C::VERSION,
+            extensibility: C::EXTENSIBILITY,
+        }
+    }
+
+    /// Returns whether the data is padded to a multiple of four bytes, with
+    /// the padding declared in the options. XCDR2 writers do so.
+    fn pads_end(self) -> bool {
+        self.version == CdrVersion::Xcdr2
+    }
+
+    /// Returns the options to write in the header, with the bits declaring
+    /// the end padding cleared if it is to be filled in after the data.
+    fn header_option(self) -> [u8; 2] {
+        let mut option = self.option;
+        if self.pads_end() {
+            option[1] &= !OPTIONS_PADDING_MASK;
+        }
+        option
+    }
+}
+
+/// Returns the number of bytes padding data of `size` bytes to a multiple of
+/// four.
+fn end_padding(size: u64) -> u64 {
+    (4 - size % 4) % 4
+}
+
+impl From<EncapsulationHeader> for Scheme {
+    fn from(header: EncapsulationHeader) -> Self {
+        let kind = header.kind;
+        let mut option = header.options;
+        // The padding declared for data read with the header does not apply
+        // to the data to be written.
+        option[1] &= !OPTIONS_PADDING_MASK;
+        Self {
+            id: kind.id(),
+            option,
+            version: kind.version(),
+            extensibility: kind.extensibility(),
+        }
+    }
+}
 
-    serialize_into::<_, _, _, C>(&mut writer, value, Infinite)?;
+fn serialize_scheme<T, S, E>(value: &T, size_limit: S, scheme: Scheme) -> Result<Vec<u8>>
+where
+    T: serde::Serialize + ?Sized,
+    S: SizeLimit,
+    E: ByteOrder,
+{
+    let mut writer = Vec::new();
+    serialize_scheme_into_vec::<_, _, E>(&mut writer, value, size_limit, scheme)?;
     Ok(writer)
 }
 
-/// Serializes an object directly into a `Write` with the encapsulation.
-pub fn serialize_into<W, T, S, C>(writer: W, value: &T, size_limit: S) -> Result<()>
+fn serialize_scheme_into_vec<T, S, E>(
+    buf: &mut Vec<u8>,
+    value: &T,
+    size_limit: S,
+    scheme: Scheme,
+) -> Result<()>
 where
-    W: Write,
+    T: serde::Serialize + ?Sized,
+    S: SizeLimit,
+    E: ByteOrder,
+{
+    use crate::encapsulation::ENCAPSULATION_HEADER_SIZE;
+
+    buf.clear();
+    match write_scheme::<_, _, _, E>(&mut *buf, value, size_limit, scheme, scheme.header_option()) {
+        Ok(padding) => {
+            // The end padding is declared in the last byte of the header,
+            // which can be filled in once the data has been written.
+            buf[ENCAPSULATION_HEADER_SIZE as usize - 1] |= padding as u8;
+            Ok(())
+        }
+        Err(e) => {
+            buf.clear();
+            Err(e)
+        }
+    }
+}
+
+fn serialize_scheme_into<W, T, S, E>(
+    mut writer: W,
+    value: &T,
+    size_limit: S,
+    scheme: Scheme,
+) -> Result<()>
+where
+    W: ser::CdrWrite,
     T: serde::ser::Serialize + ?Sized,
     S: SizeLimit,
-    C: Encapsulation,
+    E: ByteOrder,
 {
-    if let Some(limit) = size_limit.limit() {
-        calc_serialized_size_bounded(value, limit)?;
+    if !scheme.pads_end() && size_limit.limit().is_none() {
+        write_scheme::<_, _, _, E>(writer, value, size_limit, scheme, scheme.option)?;
+        return Ok(());
     }
 
-    let mut serializer = Serializer::<_, C::E>::new(writer);
+    // The header declaring the end padding goes out first, and nothing is to
+    // be written if the size limit is exceeded, so the data is serialized
+    // into a buffer before being written.
+    let mut buf = Vec::new();
+    serialize_scheme_into_vec::<_, _, E>(&mut buf, value, size_limit, scheme)?;
+    writer.write_bytes(&buf)
+}
 
-    serde::Serialize::serialize(&C::ID, &mut serializer)?;
-    serde::Serialize::serialize(&C::OPTION, &mut serializer)?;
+/// Writes the header with `option` followed by the data and, for XCDR2, the
+/// end padding, returning the length of the end padding.
+fn write_scheme<W, T, S, E>(
+    writer: W,
+    value: &T,
+    size_limit: S,
+    scheme: Scheme,
+    option: [u8; 2],
+) -> Result<usize>
+where
+    W: ser::CdrWrite,
+    T: serde::ser::Serialize + ?Sized,
+    S: SizeLimit,
+    E: ByteOrder,
+{
+    // The header must fit in the size limit too, which otherwise applies to
+    // the data.
+    let mut serializer = Serializer::<_, E>::new(writer)
+        .with_version(scheme.version)
+        .with_extensibility(scheme.extensibility)
+        .with_size_limit(size_limit);
+    serde::Serialize::serialize(&scheme.id, &mut serializer)?;
+    serde::Serialize::serialize(&option, &mut serializer)?;
     serializer.reset_pos();
-    serde::Serialize::serialize(value, &mut serializer)
+    serde::Serialize::serialize(value, &mut serializer)?;
+    if scheme.pads_end() {
+        serializer.write_end_padding()
+    } else {
+        Ok(0)
+    }
+}
+
+fn serialize_scheme_into_slice<T, S, E>(
+    buf: &mut [u8],
+    value: &T,
+    size_limit: S,
+    scheme: Scheme,
+) -> Result<usize>
+where
+    T: serde::ser::Serialize + ?Sized,
+    S: SizeLimit,
+    E: ByteOrder,
+{
+    use crate::encapsulation::ENCAPSULATION_HEADER_SIZE;
+
+    let len = buf.len();
+    let limit = size_limit.limit();
+    let mut writer = &mut *buf;
+    let option = scheme.header_option();
+    match write_scheme::<_, _, _, E>(&mut writer, value, size_limit, scheme, option) {
+        Ok(padding) => {
+            let written = len - writer.len();
+            buf[ENCAPSULATION_HEADER_SIZE as usize - 1] |= padding as u8;
+            Ok(written)
+        }
+        Err(e) if e.is_write_zero() => {
+            // Only now is the value measured, to tell how large a buffer it
+            // needs.
+            let needed = match limit {
+                Some(limit) => calc_encapsulated_size_bounded(value, limit, scheme)?,
+                None => calc_encapsulated_size(value, scheme)?,
+            };
+            Err(Error::BufferTooSmall { needed })
+        }
+        Err(e) => Err(e),
+    }
+}
+
+fn calc_encapsulated_size<T>(value: &T, scheme: Scheme) -> Result<u64>
+where
+    T: serde::Serialize + ?Sized,
+{
+    use crate::encapsulation::ENCAPSULATION_HEADER_SIZE;
+
+    let mut size = size::calc_serialized_data_size_with(
+        value,
+        scheme.version,
+        scheme.extensibility,
+        usize::MAX,
+    )?;
+    if scheme.pads_end() {
+        size += end_padding(size);
+    }
+    Ok(size + ENCAPSULATION_HEADER_SIZE)
+}
+
+fn calc_encapsulated_size_bounded<T>(value: &T, max: u64, scheme: Scheme) -> Result<u64>
+where
+    T: serde::Serialize + ?Sized,
+{
+    use crate::encapsulation::ENCAPSULATION_HEADER_SIZE;
+
+    if max < ENCAPSULATION_HEADER_SIZE {
+        Err(Error::SizeLimit)
+    } else {
+        let mut size = size::calc_serialized_data_size_bounded_with(
+            value,
+            max,
+            scheme.version,
+            scheme.extensibility,
+            usize::MAX,
+        )?;
+        if scheme.pads_end() {
+            size += end_padding(size);
+            if size > max {
+                return Err(Error::SizeLimit);
+            }
+        }
+        Ok(size + ENCAPSULATION_HEADER_SIZE)
+    }
 }
 
 /// Deserializes a slice of bytes into an object.
-pub fn deserialize<'de, T>(bytes: &[u8]) -> Result<T>
+///
+/// Strings and byte sequences can be borrowed from `bytes`.
+pub fn deserialize<'de, T>(bytes: &'de [u8]) -> Result<T>
+where
+    T: serde::Deserialize<'de>,
+{
+    deserialize_with_header(bytes).map(|(value, _)| value)
+}
+
+/// Deserializes a slice of bytes into an object, failing if any bytes are
+/// left over after it and the padding declared in the encapsulation options.
+pub fn deserialize_strict<'de, T>(bytes: &'de [u8]) -> Result<T>
+where
+    T: serde::Deserialize<'de>,
+{
+    deserialize_encapsulated(Deserializer::from_slice(bytes, Infinite), true)
+        .map(|(value, _)| value)
+}
+
+/// Deserializes a slice of bytes into an object, checking padding as
+/// `padding_mode` tells.
+pub fn deserialize_with_padding_mode<'de, T>(
+    bytes: &'de [u8],
+    padding_mode: PaddingMode,
+) -> Result<T>
+where
+    T: serde::Deserialize<'de>,
+{
+    let deserializer = Deserializer::from_slice(bytes, Infinite).with_padding_mode(padding_mode);
+    deserialize_encapsulated(deserializer, false).map(|(value, _)| value)
+}
+
+/// Deserializes a slice of bytes into an object, allowing compound values,
+/// options and newtypes to nest `max_depth` levels deep instead of
+/// `size::DEFAULT_MAX_DEPTH`.
+pub fn deserialize_with_max_depth<'de, T>(bytes: &'de [u8], max_depth: usize) -> Result<T>
+where
+    T: serde::Deserialize<'de>,
+{
+    let deserializer = Deserializer::from_slice(bytes, Infinite).with_max_depth(max_depth);
+    deserialize_encapsulated(deserializer, false).map(|(value, _)| value)
+}
+
+/// Deserializes a slice of bytes into an object, wrapping an error in
+/// `Error::Positioned` with the offset of the value that failed to decode and
+/// the path leading to it.
+pub fn deserialize_with_error_positions<'de, T>(bytes: &'de [u8]) -> Result<T>
 where
     T: serde::Deserialize<'de>,
 {
-    deserialize_from::<_, _, _>(bytes, Infinite)
+    let deserializer = Deserializer::from_slice(bytes, Infinite).with_error_positions(true);
+    deserialize_encapsulated(deserializer, false).map(|(value, _)| value)
 }
 
-/// Deserializes an object directly from a `Read`.
+/// Deserializes a slice of bytes into an object, also returning the
+/// encapsulation header it was read with.
+pub fn deserialize_with_header<'de, T>(bytes: &'de [u8]) -> Result<(T, EncapsulationHeader)>
+where
+    T: serde::Deserialize<'de>,
+{
+    deserialize_encapsulated(Deserializer::from_slice(bytes, Infinite), false)
+}
+
+/// Deserializes an object directly from a `CdrRead`.
 pub fn deserialize_from<'de, R, T, S>(reader: R, size_limit: S) -> Result<T>
 where
-    R: Read,
+    R: de::CdrRead<'de>,
     T: serde::Deserialize<'de>,
     S: SizeLimit,
 {
-    use crate::encapsulation::ENCAPSULATION_HEADER_SIZE;
+    deserialize_from_with_header(reader, size_limit).map(|(value, _)| value)
+}
 
-    let mut deserializer = Deserializer::<_, S, BigEndian>::new(reader, size_limit);
+/// Deserializes an object directly from a `CdrRead`, also returning the
+/// encapsulation header it was read with.
+pub fn deserialize_from_with_header<'de, R, T, S>(
+    reader: R,
+    size_limit: S,
+) -> Result<(T, EncapsulationHeader)>
+where
+    R: de::CdrRead<'de>,
+    T: serde::Deserialize<'de>,
+    S: SizeLimit,
+{
+    deserialize_encapsulated(Deserializer::new(reader, size_limit), false)
+}
+
+fn deserialize_encapsulated<'de, R, T, S>(
+    mut deserializer: Deserializer<R, S, BigEndian>,
+    strict: bool,
+) -> Result<(T, EncapsulationHeader)>
+where
+    R: de::CdrRead<'de>,
+    T: serde::Deserialize<'de>,
+    S: SizeLimit,
+{
+    use crate::encapsulation::ENCAPSULATION_HEADER_SIZE;
 
     let v: [u8; ENCAPSULATION_HEADER_SIZE as usize] =
         serde::Deserialize::deserialize(&mut deserializer)?;
     deserializer.reset_pos();
-    match v[1] {
-        0 | 2 => serde::Deserialize::deserialize(&mut deserializer),
-        1 | 3 => serde::Deserialize::deserialize(
-            &mut Into::<Deserializer<_, _, LittleEndian>>::into(deserializer),
-        ),
-        _ => Err(Error::InvalidEncapsulation),
+    let header = EncapsulationHeader::from_bytes(v)?;
+    let kind = header.kind;
+    let deserializer = deserializer
+        .with_version(kind.version())
+        .with_extensibility(kind.extensibility());
+    let value = match kind.endianness() {
+        Endianness::Big => deserialize_padded(deserializer, header.padding(), strict)?,
+        Endianness::Little => deserialize_padded(
+            Into::<Deserializer<_, _, LittleEndian>>::into(deserializer),
+            header.padding(),
+            strict,
+        )?,
+    };
+    Ok((value, header))
+}
+
+/// Deserializes an object followed by `padding` bytes and, if `strict`, by
+/// nothing else.
+fn deserialize_padded<'de, R, T, S, E>(
+    mut deserializer: Deserializer<R, S, E>,
+    padding: usize,
+    strict: bool,
+) -> Result<T>
+where
+    R: de::CdrRead<'de>,
+    T: serde::Deserialize<'de>,
+    S: SizeLimit,
+    E: ByteOrder,
+{
+    let value = deserializer.deserialize_value()?;
+    deserializer.read_end_padding(padding)?;
+    if strict {
+        deserializer.end()?;
     }
+    Ok(value)
 }
@@ -0,0 +1,186 @@
+//! Parameter ids, member headers and member ids used by the ParameterList
+//! encodings of mutable types.
+//!
+//! Members of a mutable struct are identified by their member id. Ids are
+//! assigned sequentially in declaration order starting from zero, as IDL
+//! `@autoid(SEQUENTIAL)` does. A field named `name@id`, where `id` is decimal
+//! or `0x`-prefixed hexadecimal, takes `id` as its member id and following
+//! fields continue counting from it, as IDL `@id` does.
+//!
+//! A member wrapped in `Key` is written with the must-understand flag of its
+//! header set, as XTypes requires of key members. Members read with that flag
+//! set are rejected only if they are unknown.
+//!
+//! Fields omitted with `#[serde(skip_serializing_if = "...")]` keep their ids,
+//! whereas fields marked `#[serde(skip_serializing)]` are invisible to the
+//! serializer and shift the ids of the following fields.
+//!
+//! `Option` fields are optional members. Absent members of mutable structs are
+//! left out, while those of other structs are written as a parameter header
+//! with a zero length in XCDR1 and as a `false` presence flag in XCDR2. As a
+//! zero length marks an absent member, a present member of zero size, such as
+//! `Some(())`, cannot be written in XCDR1. An `Option` anywhere else, at the
+//! top level, in a collection, a tuple or an enum variant, is preceded by a
+//! boolean presence flag in both versions.
+
+/// Flag of a short parameter id marking a member as must-understand.
+pub(crate) const PID_MUST_UNDERSTAND: u16 = 0x4000;
+
+/// Flag of an extended parameter id marking a member as must-understand.
+pub(crate) const PID_EXTENDED_MUST_UNDERSTAND: u32 = 0x4000_0000;
+
+/// Parameter id announcing an extended parameter header.
+pub(crate) const PID_EXTENDED: u16 = 0x3f01;
+
+/// Parameter id terminating a parameter list.
+pub(crate) const PID_SENTINEL: u16 = 0x3f02;
+
+/// Bits of a short parameter id holding the member id.
+pub(crate) const PID_ID_MASK: u16 = 0x3fff;
+
+/// Bits of an extended parameter id holding the member id.
+pub(crate) const PID_EXTENDED_ID_MASK: u32 = 0x0fff_ffff;
+
+/// Member ids from this value upward need an extended parameter header.
+pub(crate) const PID_SHORT_ID_LIMIT: u32 = 0x3f00;
+
+/// Flag of an EMHEADER marking a member as must-understand.
+pub(crate) const EMHEADER_MUST_UNDERSTAND: u32 = 0x8000_0000;
+
+/// Bits of an EMHEADER holding the member id.
+pub(crate) const EMHEADER_ID_MASK: u32 = 0x0fff_ffff;
+
+/// Position of the length code in an EMHEADER.
+pub(crate) const EMHEADER_LC_SHIFT: u32 = 28;
+
+/// Length code telling that a NEXTINT holding the member length follows the
+/// EMHEADER.
+pub(crate) const LC_NEXTINT: u32 = 4;
+
+/// How the member being written starts, which decides the length code of its
+/// EMHEADER.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum Lead {
+    /// Nothing has been written yet.
+    Start,
+    /// The member starts with the length or element count of a string or a
+    /// sequence.
+    Prefix(u32),
+    /// The member starts with a DHEADER.
+    Delimited,
+    /// The member starts with anything else.
+    Other,
+}
+
+impl Lead {
+    /// Returns the length code for a member of `len` bytes. Members starting
+    /// with a length from which their size can be derived reuse it as NEXTINT
+    /// (length codes 5 to 7), and those of 1, 2, 4 or 8 bytes need no NEXTINT
+    /// (length codes 0 to 3).
+    pub(crate) fn length_code(self, len: u64) -> u32 {
+        match self {
+            Lead::Delimited => 5,
+            Lead::Prefix(n) if len == 4 + u64::from(n) => 5,
+            Lead::Prefix(n) if len == 4 + u64::from(n) * 4 => 6,
+            Lead::Prefix(n) if len == 4 + u64::from(n) * 8 => 7,
+            Lead::Prefix(_) => LC_NEXTINT,
+            _ => match len {
+                1 => 0,
+                2 => 1,
+                4 => 2,
+                8 => 3,
+                _ => LC_NEXTINT,
+            },
+        }
+    }
+}
+
+/// Where an `Option` is being serialized or deserialized, which decides how
+/// it is encoded.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum Optional {
+    /// The value is not directly a struct member and is preceded by a
+    /// boolean presence flag.
+    Value,
+    /// The value is a member of a final or appendable struct with the given
+    /// member id.
+    Member(u32),
+    /// The value is a member of a mutable struct, which is left out when
+    /// absent.
+    Parameter,
+    /// The member of a mutable struct turned out to be absent.
+    Absent,
+}
+
+/// Assigns member ids to the fields of a struct in declaration order.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct MemberIds {
+    next: u32,
+}
+
+impl MemberIds {
+    /// Returns the member id of the next field.
+    pub(crate) fn next(&mut self, name: &str) -> u32 {
+        let id = explicit_member_id(name).unwrap_or(self.next);
+        self.next = id.wrapping_add(1);
+        id
+    }
+}
+
+/// Returns the index of the field with the given member id.
+pub(crate) fn find_member(fields: &[&str], id: u32) -> Option<usize> {
+    let mut ids = MemberIds::default();
+    fields.iter().position(|name| ids.next(name) == id)
+}
+
+/// Returns the name of a field without the member id it may end with.
+pub(crate) fn field_name(name: &str) -> &str {
+    match name.rsplit_once('@') {
+        Some((field, _)) if explicit_member_id(name).is_some() => field,
+        _ => name,
+    }
+}
+
+fn explicit_member_id(name: &str) -> Option<u32> {
+    let (_, id) = name.rsplit_once('@')?;
+    match id.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => id.parse().ok(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_member_ids() {
+        let fields = ["a", "b@10", "c", "d@0x20", "e"];
+        let mut ids = MemberIds::default();
+        let ids: Vec<_> = fields.iter().map(|name| ids.next(name)).collect();
+        assert_eq!(ids, vec![0, 10, 11, 0x20, 0x21]);
+
+        assert_eq!(find_member(&fields, 11), Some(2));
+        assert_eq!(find_member(&fields, 0x21), Some(4));
+        assert_eq!(find_member(&fields, 1), None);
+
+        assert_eq!(field_name("b@10"), "b");
+        assert_eq!(field_name("d@0x20"), "d");
+        assert_eq!(field_name("e"), "e");
+        assert_eq!(field_name("f@g"), "f@g");
+    }
+
+    #[test]
+    fn test_length_code() {
+        assert_eq!(Lead::Other.length_code(1), 0);
+        assert_eq!(Lead::Other.length_code(2), 1);
+        assert_eq!(Lead::Other.length_code(4), 2);
+        assert_eq!(Lead::Other.length_code(8), 3);
+        assert_eq!(Lead::Other.length_code(12), LC_NEXTINT);
+        assert_eq!(Lead::Delimited.length_code(8), 5);
+        assert_eq!(Lead::Prefix(6).length_code(10), 5);
+        assert_eq!(Lead::Prefix(3).length_code(16), 6);
+        assert_eq!(Lead::Prefix(3).length_code(28), 7);
+        assert_eq!(Lead::Prefix(3).length_code(10), LC_NEXTINT);
+    }
+}
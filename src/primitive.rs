@@ -0,0 +1,379 @@
+//! Sequences and arrays of primitive values encoded in bulk.
+
+use alloc::{vec, vec::Vec};
+use core::{fmt, marker::PhantomData, ops::Deref};
+
+use byteorder::{ByteOrder, NativeEndian};
+use serde::{
+    de,
+    ser::{self, SerializeTuple},
+};
+
+/// Upper bound on the capacity reserved from a length hint given by another
+/// format.
+const MAX_PREALLOCATION: usize = 4096;
+
+/// Names of sequences of elements of 1, 2, 4 and 8 bytes.
+const SEQUENCE_NAMES: [&str; 4] = [
+    "$cdr::Primitives1",
+    "$cdr::Primitives2",
+    "$cdr::Primitives4",
+    "$cdr::Primitives8",
+];
+
+/// Names of arrays of elements of 1, 2, 4 and 8 bytes.
+const ARRAY_NAMES: [&str; 4] = [
+    "$cdr::PrimitiveArray1",
+    "$cdr::PrimitiveArray2",
+    "$cdr::PrimitiveArray4",
+    "$cdr::PrimitiveArray8",
+];
+
+/// How a run of primitive values is laid out, with the size of its elements.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum Bulk {
+    /// A sequence, prefixed with its number of elements.
+    Sequence(usize),
+    /// An array, whose number of elements is known from its type.
+    Array(usize),
+}
+
+impl Bulk {
+    /// Returns the layout that a newtype or a tuple struct with `name` stands
+    /// for, if any.
+    pub(crate) fn of(name: &str) -> Option<Bulk> {
+        if let Some(i) = SEQUENCE_NAMES.iter().position(|&n| n == name) {
+            Some(Bulk::Sequence(1 << i))
+        } else {
+            ARRAY_NAMES
+                .iter()
+                .position(|&n| n == name)
+                .map(|i| Bulk::Array(1 << i))
+        }
+    }
+
+    pub(crate) fn size(self) -> usize {
+        match self {
+            Bulk::Sequence(size) | Bulk::Array(size) => size,
+        }
+    }
+}
+
+/// Returns whether the byte order `E` is that of the host.
+pub(crate) fn is_native<E>() -> bool
+where
+    E: ByteOrder,
+{
+    E::read_u16(&[0, 1]) == NativeEndian::read_u16(&[0, 1])
+}
+
+/// Reverses the bytes of each element of `size` bytes.
+pub(crate) fn swap_bytes(bytes: &mut [u8], size: usize) {
+    if size > 1 {
+        for element in bytes.chunks_exact_mut(size) {
+            element.reverse();
+        }
+    }
+}
+
+mod private {
+    pub trait Sealed {}
+}
+
+/// A primitive type whose sequences and arrays can be encoded in bulk.
+///
+/// It is implemented for the integer and floating point types.
+pub trait Primitive:
+    Copy + Default + ser::Serialize + for<'de> de::Deserialize<'de> + private::Sealed
+{
+    #[doc(hidden)]
+    fn read_ne(src: &[u8], dst: &mut [Self]);
+}
+
+impl private::Sealed for u8 {}
+
+impl Primitive for u8 {
+    fn read_ne(src: &[u8], dst: &mut [u8]) {
+        dst.copy_from_slice(src);
+    }
+}
+
+impl private::Sealed for i8 {}
+
+impl Primitive for i8 {
+    fn read_ne(src: &[u8], dst: &mut [i8]) {
+        for (d, s) in dst.iter_mut().zip(src) {
+            *d = *s as i8;
+        }
+    }
+}
+
+macro_rules! impl_primitive {
+    ($ty:ty = $read_method:ident) => {
+        impl private::Sealed for $ty {}
+
+        impl Primitive for $ty {
+            fn read_ne(src: &[u8], dst: &mut [$ty]) {
+                NativeEndian::$read_method(src, dst);
+            }
+        }
+    };
+}
+
+impl_primitive!(i16 = read_i16_into);
+impl_primitive!(u16 = read_u16_into);
+impl_primitive!(i32 = read_i32_into);
+impl_primitive!(u32 = read_u32_into);
+impl_primitive!(i64 = read_i64_into);
+impl_primitive!(u64 = read_u64_into);
+impl_primitive!(f32 = read_f32_into);
+impl_primitive!(f64 = read_f64_into);
+
+fn name_of<T>(names: &[&'static str; 4]) -> &'static str {
+    names[core::mem::size_of::<T>().trailing_zeros() as usize]
+}
+
+fn from_ne_bytes<T>(bytes: &[u8]) -> Option<Vec<T>>
+where
+    T: Primitive,
+{
+    let size = core::mem::size_of::<T>();
+    if bytes.len() % size != 0 {
+        return None;
+    }
+
+    let mut values = vec![T::default(); bytes.len() / size];
+    T::read_ne(bytes, &mut values);
+    Some(values)
+}
+
+/// Elements that other formats see as a plain sequence, and which the CDR
+/// serializer gathers into chunks written at once.
+struct Elements<'a, T>(&'a [T]);
+
+impl<T> ser::Serialize for Elements<'_, T>
+where
+    T: Primitive,
+{
+    fn serialize<S>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        serializer.collect_seq(self.0)
+    }
+}
+
+/// Elements that other formats see as a plain array, and which the CDR
+/// serializer gathers into chunks written at once.
+struct ArrayElements<'a, T>(&'a [T]);
+
+impl<T> ser::Serialize for ArrayElements<'_, T>
+where
+    T: Primitive,
+{
+    fn serialize<S>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        let mut state = serializer.serialize_tuple(self.0.len())?;
+        for value in self.0 {
+            state.serialize_element(value)?;
+        }
+        state.end()
+    }
+}
+
+/// Reads elements handed over by the CDR deserializer as bytes in the byte
+/// order of the host, or by other formats one by one.
+struct ValuesVisitor<T> {
+    len: Option<usize>,
+    phantom: PhantomData<T>,
+}
+
+impl<T> ValuesVisitor<T> {
+    fn new(len: Option<usize>) -> Self {
+        ValuesVisitor {
+            len,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<'de, T> de::Visitor<'de> for ValuesVisitor<T>
+where
+    T: Primitive,
+{
+    type Value = Vec<T>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self.len {
+            Some(len) => write!(formatter, "an array of {} primitive values", len),
+            None => formatter.write_str("a sequence of primitive values"),
+        }
+    }
+
+    fn visit_newtype_struct<D>(self, deserializer: D) -> core::result::Result<Vec<T>, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        match self.len {
+            Some(len) => deserializer.deserialize_tuple(len, self),
+            None => deserializer.deserialize_seq(self),
+        }
+    }
+
+    fn visit_bytes<E>(self, v: &[u8]) -> core::result::Result<Vec<T>, E>
+    where
+        E: de::Error,
+    {
+        let values = from_ne_bytes(v).ok_or_else(|| de::Error::invalid_length(v.len(), &self))?;
+        match self.len {
+            Some(len) if len != values.len() => Err(de::Error::invalid_length(values.len(), &self)),
+            _ => Ok(values),
+        }
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> core::result::Result<Vec<T>, A::Error>
+    where
+        A: de::SeqAccess<'de>,
+    {
+        let hint = self.len.or_else(|| seq.size_hint()).unwrap_or(0);
+        let mut values = Vec::with_capacity(hint.min(MAX_PREALLOCATION));
+        while let Some(value) = seq.next_element()? {
+            values.push(value);
+        }
+        match self.len {
+            Some(len) if len != values.len() => Err(de::Error::invalid_length(values.len(), &self)),
+            _ => Ok(values),
+        }
+    }
+}
+
+/// A sequence of primitive values that is encoded in bulk rather than element
+/// by element.
+///
+/// It is encoded in CDR exactly like a `Vec<T>`, and other formats see it as
+/// a sequence of its elements.
+#[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Primitives<T>(pub Vec<T>);
+
+impl<T> From<Vec<T>> for Primitives<T> {
+    fn from(v: Vec<T>) -> Self {
+        Primitives(v)
+    }
+}
+
+impl<T> Deref for Primitives<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        &self.0
+    }
+}
+
+impl<T> ser::Serialize for Primitives<T>
+where
+    T: Primitive,
+{
+    fn serialize<S>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        serializer.serialize_newtype_struct(name_of::<T>(&SEQUENCE_NAMES), &Elements(&self.0))
+    }
+}
+
+impl<'de, T> de::Deserialize<'de> for Primitives<T>
+where
+    T: Primitive,
+{
+    fn deserialize<D>(deserializer: D) -> core::result::Result<Self, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        deserializer
+            .deserialize_newtype_struct(name_of::<T>(&SEQUENCE_NAMES), ValuesVisitor::new(None))
+            .map(Primitives)
+    }
+}
+
+/// An array of primitive values that is encoded in bulk rather than element
+/// by element.
+///
+/// It is encoded in CDR exactly like a `[T; N]`, and other formats see it as
+/// an array of its elements.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct PrimitiveArray<T, const N: usize>(pub [T; N]);
+
+impl<T, const N: usize> From<[T; N]> for PrimitiveArray<T, N> {
+    fn from(v: [T; N]) -> Self {
+        PrimitiveArray(v)
+    }
+}
+
+impl<T, const N: usize> Deref for PrimitiveArray<T, N> {
+    type Target = [T; N];
+
+    fn deref(&self) -> &[T; N] {
+        &self.0
+    }
+}
+
+impl<T, const N: usize> ser::Serialize for PrimitiveArray<T, N>
+where
+    T: Primitive,
+{
+    fn serialize<S>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        serializer.serialize_newtype_struct(name_of::<T>(&ARRAY_NAMES), &ArrayElements(&self.0))
+    }
+}
+
+impl<'de, T, const N: usize> de::Deserialize<'de> for PrimitiveArray<T, N>
+where
+    T: Primitive,
+{
+    fn deserialize<D>(deserializer: D) -> core::result::Result<Self, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        let values = deserializer
+            .deserialize_newtype_struct(name_of::<T>(&ARRAY_NAMES), ValuesVisitor::new(Some(N)))?;
+        let len = values.len();
+        <[T; N]>::try_from(values)
+            .map(PrimitiveArray)
+            .map_err(|_| de::Error::invalid_length(len, &"an array of primitive values"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use byteorder::{BigEndian, LittleEndian};
+
+    use super::*;
+
+    #[test]
+    fn test_bulk_of() {
+        assert_eq!(
+            Bulk::of(name_of::<u8>(&SEQUENCE_NAMES)),
+            Some(Bulk::Sequence(1))
+        );
+        assert_eq!(
+            Bulk::of(name_of::<f32>(&SEQUENCE_NAMES)),
+            Some(Bulk::Sequence(4))
+        );
+        assert_eq!(Bulk::of(name_of::<i16>(&ARRAY_NAMES)), Some(Bulk::Array(2)));
+        assert_eq!(Bulk::of(name_of::<f64>(&ARRAY_NAMES)), Some(Bulk::Array(8)));
+        assert_eq!(Bulk::of("Primitives"), None);
+    }
+
+    #[test]
+    fn test_swap_bytes() {
+        assert!(is_native::<BigEndian>() != is_native::<LittleEndian>());
+        let mut bytes = [1, 2, 3, 4, 5, 6, 7, 8];
+        swap_bytes(&mut bytes, 4);
+        assert_eq!(bytes, [4, 3, 2, 1, 8, 7, 6, 5]);
+    }
+}
@@ -1,88 +1,575 @@
 //! Serializing Rust data types into CDR.
 
-use std::{self, io::Write, marker::PhantomData};
+mod bulk;
+mod write;
 
-use byteorder::{ByteOrder, WriteBytesExt};
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
 use serde::ser;
 
 use crate::{
+    encapsulated::{byte_order_flag, ENCAPSULATED_NAME},
+    encapsulation::{CdrVersion, EncapsulationKind, Endianness, Extensibility},
     error::{Error, Result},
-    size::{calc_serialized_data_size, calc_serialized_data_size_bounded, Infinite, SizeLimit},
+    parameter::{
+        Lead, MemberIds, Optional, EMHEADER_ID_MASK, EMHEADER_LC_SHIFT, EMHEADER_MUST_UNDERSTAND,
+        LC_NEXTINT, PID_EXTENDED, PID_EXTENDED_ID_MASK, PID_EXTENDED_MUST_UNDERSTAND,
+        PID_MUST_UNDERSTAND, PID_SENTINEL, PID_SHORT_ID_LIMIT,
+    },
+    primitive::Bulk,
+    size::{calc_serialized_data_size_bounded, calc_serialized_data_size_with, SizeLimit},
+    wide::{encode_utf16, encode_wchar, WideEncoding, WCHAR_NAME, WSTRING_NAME},
+    xtypes::{extensibility_of, DELIMITED_NAME, KEY_NAME},
 };
 
+use self::bulk::BulkWriter;
+pub use self::write::CdrWrite;
+
+const PADDING: [u8; 8] = [0; 8];
+
 /// A serializer that writes values into a buffer.
 pub struct Serializer<W, E> {
     writer: W,
+    /// Position of the first byte written, restored by `reset`.
+    start: u64,
     pos: u64,
+    version: CdrVersion,
+    extensibility: Extensibility,
+    /// Whether a struct or an enum is being written, whose nested structs are
+    /// final unless annotated otherwise.
+    in_aggregate: bool,
+    buffers: Vec<Vec<u8>>,
+    lead: Lead,
+    optional: Optional,
+    ascii_only: bool,
+    wide_encoding: WideEncoding,
+    wide: bool,
+    /// Whether the next sequence, array or map is preceded by a DHEADER, as
+    /// `Delimited` asks.
+    delimit: bool,
+    /// Extensibility kind of the next struct, as `Final`, `Appendable` or
+    /// `Mutable` gives it.
+    annotated: Option<Extensibility>,
+    /// Whether the member of a mutable struct being written is wrapped in
+    /// `Key`, which sets the must-understand flag of its header.
+    key: bool,
+    depth: usize,
+    max_depth: usize,
+    size_limit: Option<u64>,
+    /// Bytes written or buffered since the start of the data.
+    size: u64,
     phantom: PhantomData<E>,
 }
 
 impl<W, E> Serializer<W, E>
 where
-    W: Write,
+    W: CdrWrite,
     E: ByteOrder,
 {
     pub fn new(writer: W) -> Self {
+        Self::new_at(writer, 0)
+    }
+
+    /// Creates a serializer whose output starts `pos` bytes after the origin
+    /// that alignment is relative to, as when it is to be spliced into a
+    /// larger CDR stream at that offset.
+    pub fn new_at(writer: W, pos: u64) -> Self {
         Self {
             writer,
-            pos: 0,
+            start: pos,
+            pos,
+            version: CdrVersion::default(),
+            extensibility: Extensibility::default(),
+            in_aggregate: false,
+            buffers: Vec::new(),
+            lead: Lead::Other,
+            optional: Optional::Value,
+            ascii_only: false,
+            wide_encoding: WideEncoding::default(),
+            wide: false,
+            delimit: false,
+            annotated: None,
+            key: false,
+            depth: 0,
+            max_depth: usize::MAX,
+            size_limit: None,
+            size: 0,
             phantom: PhantomData,
         }
     }
 
+    /// Sets the version of the CDR representation to write.
+    pub fn with_version(mut self, version: CdrVersion) -> Self {
+        self.version = version;
+        self
+    }
+
+    /// Sets the extensibility kind applied to the outermost structs.
+    pub fn with_extensibility(mut self, extensibility: Extensibility) -> Self {
+        self.extensibility = extensibility;
+        self
+    }
+
+    /// Rejects strings containing non-ASCII characters, which are otherwise
+    /// written as UTF-8.
+    pub fn with_ascii_only(mut self, ascii_only: bool) -> Self {
+        self.ascii_only = ascii_only;
+        self
+    }
+
+    /// Sets the conventions for writing `WChar` and `WString`.
+    pub fn with_wide_encoding(mut self, wide_encoding: WideEncoding) -> Self {
+        self.wide_encoding = wide_encoding;
+        self
+    }
+
+    /// Sets the deepest nesting of compound values, options and newtypes
+    /// allowed before failing with `Error::RecursionLimitExceeded`. There is
+    /// no limit by default.
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Fails with `Error::SizeLimit` as soon as the output would exceed
+    /// `size_limit`, without measuring the value beforehand. Output already
+    /// written is left in the writer.
+    pub fn with_size_limit<S>(mut self, size_limit: S) -> Self
+    where
+        S: SizeLimit,
+    {
+        self.size_limit = size_limit.limit();
+        self
+    }
+
+    /// Returns a reference to the writer.
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    /// Returns a mutable reference to the writer.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    /// Unwraps the writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    /// Clears the position and the output buffered for unfinished sections,
+    /// as well as the state left behind by a value that failed to serialize.
+    fn reset_state(&mut self) {
+        self.pos = self.start;
+        self.size = 0;
+        self.in_aggregate = false;
+        self.buffers.clear();
+        self.lead = Lead::Other;
+        self.optional = Optional::Value;
+        self.wide = false;
+        self.delimit = false;
+        self.annotated = None;
+        self.key = false;
+        self.depth = 0;
+    }
+
+    /// Enters a compound value, an option or a newtype.
+    fn enter(&mut self) -> Result<()> {
+        if self.depth >= self.max_depth {
+            return Err(Error::RecursionLimitExceeded);
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    fn leave(&mut self) {
+        self.depth -= 1;
+    }
+
+    /// Serializes a value nested in an option or a newtype.
+    fn serialize_nested<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ser::Serialize + ?Sized,
+    {
+        self.enter()?;
+        value.serialize(&mut *self)?;
+        self.leave();
+        Ok(())
+    }
+
     fn add_pos(&mut self, size: u64) {
         self.pos += size;
     }
 
     pub(crate) fn reset_pos(&mut self) {
         self.pos = 0;
+        self.size = 0;
+    }
+
+    /// Writes the padding that brings encapsulated data to a multiple of four
+    /// bytes, returning its length.
+    pub(crate) fn write_end_padding(&mut self) -> Result<usize> {
+        let amt = (4 - self.pos as usize % 4) % 4;
+        self.pos += amt as u64;
+        self.write_all(&PADDING[..amt])?;
+        Ok(amt)
     }
 
     fn set_pos_of<T>(&mut self) -> Result<()> {
         self.write_padding_of::<T>()?;
-        self.add_pos(std::mem::size_of::<T>() as u64);
+        self.add_pos(core::mem::size_of::<T>() as u64);
         Ok(())
     }
 
-    fn write_padding_of<T>(&mut self) -> Result<()> {
+    fn padding_of<T>(&self) -> usize {
+        self.padding_for(core::mem::size_of::<T>())
+    }
+
+    fn padding_for(&self, size: usize) -> usize {
         // Calculate the required padding to align with 1-byte, 2-byte, 4-byte, 8-byte
         // boundaries Instead of using the slow modulo operation '%', the faster
         // bit-masking is used
-        const PADDING: [u8; 8] = [0; 8];
-        let alignment = std::mem::size_of::<T>();
+        let alignment = size.min(self.version.max_alignment());
         let rem_mask = alignment - 1; // mask like 0x0, 0x1, 0x3, 0x7
         match (self.pos as usize) & rem_mask {
+            0 => 0,
+            n @ 1..=7 => alignment - n,
+            _ => unreachable!(),
+        }
+    }
+
+    fn write_padding_of<T>(&mut self) -> Result<()> {
+        self.write_padding_for(core::mem::size_of::<T>())
+    }
+
+    fn write_padding_for(&mut self, size: usize) -> Result<()> {
+        match self.padding_for(size) {
             0 => Ok(()),
-            n @ 1..=7 => {
-                let amt = alignment - n;
+            amt => {
                 self.pos += amt as u64;
-                self.writer.write_all(&PADDING[..amt]).map_err(Into::into)
+                self.write_all(&PADDING[..amt])
             }
-            _ => unreachable!(),
         }
     }
 
+    fn write_all(&mut self, buf: &[u8]) -> Result<()> {
+        if self.delimit || self.annotated.is_some() {
+            // Only collections can be delimited, and only structs annotated.
+            return Err(Error::TypeNotSupported);
+        }
+        if self.lead == Lead::Start {
+            self.lead = Lead::Other;
+        }
+        if let Optional::Member(_) | Optional::Parameter = self.optional {
+            self.optional = Optional::Value;
+        }
+        self.size += buf.len() as u64;
+        if let Some(limit) = self.size_limit {
+            if self.size > limit {
+                return Err(Error::SizeLimit);
+            }
+        }
+        match self.buffers.last_mut() {
+            Some(buffer) => {
+                buffer.extend_from_slice(buf);
+                Ok(())
+            }
+            None => self.writer.write_bytes(buf),
+        }
+    }
+
+    /// Enters a struct or an enum, returning whether it is the outermost one.
+    fn enter_aggregate(&mut self) -> bool {
+        !core::mem::replace(&mut self.in_aggregate, true)
+    }
+
+    fn leave_aggregate(&mut self, outermost: bool) {
+        if outermost {
+            self.in_aggregate = false;
+        }
+    }
+
+    /// Reserves room for a DHEADER and buffers the following output until
+    /// `end_delimited` is called, so that the length can be written first.
+    fn begin_delimited(&mut self) -> Result<()> {
+        self.set_pos_of::<u32>()?;
+        if self.lead == Lead::Start {
+            self.lead = Lead::Delimited;
+        }
+        self.buffers.push(Vec::new());
+        Ok(())
+    }
+
+    /// Begins a sequence, an array or a map, with a DHEADER if `Delimited`
+    /// asks for one, returning whether it did.
+    fn begin_collection(&mut self) -> Result<bool> {
+        if self.annotated.is_some() {
+            return Err(Error::TypeNotSupported);
+        }
+        let delimited = core::mem::take(&mut self.delimit);
+        if delimited {
+            self.begin_delimited()?;
+        }
+        Ok(delimited)
+    }
+
+    /// Fails if a `Delimited` wraps something other than a collection, or a
+    /// `Final`, an `Appendable` or a `Mutable` something other than a struct.
+    fn check_unwrapped(&self) -> Result<()> {
+        if self.delimit || self.annotated.is_some() {
+            Err(Error::TypeNotSupported)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Takes the innermost buffer, whose content is counted again once it is
+    /// written out.
+    fn pop_buffer(&mut self, msg: &str) -> Vec<u8> {
+        let buffer = self.buffers.pop().expect(msg);
+        self.size -= buffer.len() as u64;
+        buffer
+    }
+
+    /// Buffers a member of a mutable struct after room for a 4-byte header,
+    /// returning its alignment padding, its content and whether it is a key,
+    /// or `None` if it is an absent optional member. The padding and header
+    /// are left for the caller to write once the member turns out to be
+    /// present.
+    fn buffer_member<T>(&mut self, value: &T) -> Result<Option<(usize, Vec<u8>, bool)>>
+    where
+        T: ser::Serialize + ?Sized,
+    {
+        let start = self.pos;
+        let padding = self.padding_of::<u32>();
+        self.pos += padding as u64 + 4;
+        self.buffers.push(Vec::new());
+        self.optional = Optional::Parameter;
+        // The member may be nested in a key member of an enclosing struct.
+        let outer_key = core::mem::take(&mut self.key);
+        let result = value.serialize(&mut *self);
+        let key = core::mem::replace(&mut self.key, outer_key);
+        let optional = core::mem::replace(&mut self.optional, Optional::Value);
+        let buffer = self.pop_buffer("unbalanced member");
+        result?;
+        if optional == Optional::Absent {
+            self.pos = start;
+            Ok(None)
+        } else {
+            Ok(Some((padding, buffer, key)))
+        }
+    }
+
+    /// Writes a member of a mutable struct preceded by a parameter header.
+    fn serialize_parameter<T>(&mut self, id: u32, value: &T) -> Result<()>
+    where
+        T: ser::Serialize + ?Sized,
+    {
+        let (padding, mut buffer, must_understand) = match self.buffer_member(value)? {
+            Some(member) => member,
+            None => return Ok(()),
+        };
+        // The length includes the padding that aligns the next header.
+        let trailing = self.padding_of::<u32>();
+        self.pos += trailing as u64;
+        buffer.extend_from_slice(&PADDING[..trailing]);
+        self.write_all(&PADDING[..padding])?;
+        self.write_parameter(id, must_understand, &buffer)
+    }
+
+    /// Writes a parameter header followed by `buffer`, assuming that room for
+    /// a short header has been reserved.
+    fn write_parameter(&mut self, id: u32, must_understand: bool, buffer: &[u8]) -> Result<()> {
+        if id < PID_SHORT_ID_LIMIT && buffer.len() <= u16::MAX as usize {
+            let mut pid = id as u16;
+            if must_understand {
+                pid |= PID_MUST_UNDERSTAND;
+            }
+            let mut header = [0; 4];
+            E::write_u16(&mut header[..2], pid);
+            E::write_u16(&mut header[2..], buffer.len() as u16);
+            self.write_all(&header)?;
+        } else {
+            if id > PID_EXTENDED_ID_MASK || buffer.len() > u32::MAX as usize {
+                return Err(Error::NumberOutOfRange);
+            }
+
+            let mut id = id;
+            if must_understand {
+                id |= PID_EXTENDED_MUST_UNDERSTAND;
+            }
+            let mut header = [0; 12];
+            E::write_u16(&mut header[..2], PID_EXTENDED);
+            E::write_u16(&mut header[2..4], 8);
+            E::write_u32(&mut header[4..8], id);
+            E::write_u32(&mut header[8..], buffer.len() as u32);
+            // The extended header is longer by a multiple of the maximum
+            // alignment, so the buffered member stays correctly aligned.
+            self.add_pos(8);
+            self.write_all(&header)?;
+        }
+        self.write_all(buffer)
+    }
+
+    /// Writes a member of a mutable struct preceded by an EMHEADER and, if
+    /// the length code requires it, a NEXTINT.
+    fn serialize_member<T>(&mut self, id: u32, value: &T) -> Result<()>
+    where
+        T: ser::Serialize + ?Sized,
+    {
+        if id > EMHEADER_ID_MASK {
+            return Err(Error::NumberOutOfRange);
+        }
+
+        let lead = core::mem::replace(&mut self.lead, Lead::Start);
+        let result = self.buffer_member(value);
+        let member_lead = core::mem::replace(&mut self.lead, lead);
+        let (padding, buffer, must_understand) = match result? {
+            Some(member) => member,
+            None => return Ok(()),
+        };
+        self.write_all(&PADDING[..padding])?;
+        let lc = member_lead.length_code(buffer.len() as u64);
+
+        let mut emheader = lc << EMHEADER_LC_SHIFT | id;
+        if must_understand {
+            emheader |= EMHEADER_MUST_UNDERSTAND;
+        }
+        let mut header = [0; 8];
+        E::write_u32(&mut header[..4], emheader);
+        if lc == LC_NEXTINT {
+            if buffer.len() > u32::MAX as usize {
+                return Err(Error::NumberOutOfRange);
+            }
+
+            E::write_u32(&mut header[4..], buffer.len() as u32);
+            // XCDR2 aligns to at most 4 bytes, so the NEXTINT does not disturb
+            // the alignment of the buffered member.
+            self.add_pos(4);
+            self.write_all(&header)?;
+        } else {
+            self.write_all(&header[..4])?;
+        }
+        self.write_all(&buffer)
+    }
+
+    fn write_sentinel(&mut self) -> Result<()> {
+        let mut header = [0; 4];
+        E::write_u16(&mut header[..2], PID_SENTINEL);
+        self.set_pos_of::<u32>()?;
+        self.write_all(&header)
+    }
+
+    fn end_delimited(&mut self) -> Result<()> {
+        let buffer = self.pop_buffer("unbalanced delimited section");
+        if buffer.len() > u32::MAX as usize {
+            return Err(Error::NumberOutOfRange);
+        }
+
+        let mut header = [0; 4];
+        E::write_u32(&mut header, buffer.len() as u32);
+        self.write_all(&header)?;
+        self.write_all(&buffer)
+    }
+
+    /// Writes `value` as an octet sequence starting with the byte-order flag,
+    /// with alignment relative to the start of the sequence.
+    fn write_encapsulated<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ser::Serialize + ?Sized,
+    {
+        self.set_pos_of::<u32>()?;
+        let pos = core::mem::replace(&mut self.pos, 1);
+        self.buffers.push(Vec::new());
+        let result = self
+            .write_all(&[byte_order_flag::<E>()])
+            .and_then(|()| self.serialize_nested(value));
+        let buffer = self.pop_buffer("unbalanced encapsulation");
+        self.pos = pos + buffer.len() as u64;
+        result?;
+        if buffer.len() > u32::MAX as usize {
+            return Err(Error::NumberOutOfRange);
+        }
+
+        let mut header = [0; 4];
+        E::write_u32(&mut header, buffer.len() as u32);
+        self.write_all(&header)?;
+        self.write_all(&buffer)
+    }
+
+    fn write_wchar(&mut self, v: char) -> Result<()> {
+        let unit = encode_wchar(v)?;
+        match self.wide_encoding {
+            WideEncoding::Xcdr => ser::Serializer::serialize_u16(&mut *self, unit),
+            WideEncoding::Giop => {
+                let mut buf = [2, 0, 0];
+                BigEndian::write_u16(&mut buf[1..], unit);
+                self.add_pos(buf.len() as u64);
+                self.write_all(&buf)
+            }
+        }
+    }
+
+    fn write_wstring(&mut self, v: &str) -> Result<()> {
+        let mut buf = match self.wide_encoding {
+            WideEncoding::Xcdr => encode_utf16::<E>(v),
+            WideEncoding::Giop => encode_utf16::<BigEndian>(v),
+        };
+        match (self.wide_encoding, self.version) {
+            (WideEncoding::Xcdr, CdrVersion::Xcdr1) => {
+                // The length counts code units, including a terminating null.
+                buf.extend_from_slice(&[0, 0]);
+                self.write_usize_as_u32(buf.len() / 2)?;
+            }
+            _ => self.write_usize_as_u32(buf.len())?,
+        }
+        self.add_pos(buf.len() as u64);
+        self.write_all(&buf)
+    }
+
     fn write_usize_as_u32(&mut self, v: usize) -> Result<()> {
-        if v > std::u32::MAX as usize {
+        if v > u32::MAX as usize {
             return Err(Error::NumberOutOfRange);
         }
 
-        ser::Serializer::serialize_u32(self, v as u32)
+        let starts_member = self.lead == Lead::Start;
+        ser::Serializer::serialize_u32(&mut *self, v as u32)?;
+        if starts_member {
+            self.lead = Lead::Prefix(v as u32);
+        }
+        Ok(())
+    }
+}
+
+impl<E> Serializer<Vec<u8>, E>
+where
+    E: ByteOrder,
+{
+    /// Clears the output and the position, as well as the state left behind by
+    /// a value that failed to serialize, so that the next value is written as
+    /// if by a new serializer reusing the capacity of the buffer.
+    pub fn reset(&mut self) {
+        self.writer.clear();
+        self.reset_state();
     }
 }
 
 macro_rules! impl_serialize_value {
     ($ser_method:ident($ty:ty) = $writer_method:ident()) => {
         fn $ser_method(self, v: $ty) -> Result<Self::Ok> {
+            let mut buf = [0; core::mem::size_of::<$ty>()];
+            E::$writer_method(&mut buf, v);
             self.set_pos_of::<$ty>()?;
-            self.writer.$writer_method::<E>(v).map_err(Into::into)
+            self.write_all(&buf)
         }
     };
 }
 
 impl<'a, W, E> ser::Serializer for &'a mut Serializer<W, E>
 where
-    W: Write,
+    W: CdrWrite,
     E: ByteOrder,
 {
     type Error = Error;
@@ -113,40 +600,44 @@ where
 
     fn serialize_bool(self, v: bool) -> Result<Self::Ok> {
         self.set_pos_of::<bool>()?;
-        self.writer.write_u8(v as u8).map_err(Into::into)
+        self.write_all(&[v as u8])
     }
 
     fn serialize_i8(self, v: i8) -> Result<Self::Ok> {
         self.set_pos_of::<i8>()?;
-        self.writer.write_i8(v).map_err(Into::into)
+        self.write_all(&[v as u8])
     }
 
     fn serialize_u8(self, v: u8) -> Result<Self::Ok> {
         self.set_pos_of::<u8>()?;
-        self.writer.write_u8(v).map_err(Into::into)
+        self.write_all(&[v])
     }
 
     fn serialize_char(self, v: char) -> Result<Self::Ok> {
-        if !v.is_ascii() {
+        if core::mem::take(&mut self.wide) {
+            self.write_wchar(v)
+        } else if !v.is_ascii() {
             Err(Error::InvalidChar(v))
         } else {
             let mut buf = [0u8; 1];
             v.encode_utf8(&mut buf);
             self.add_pos(1);
-            self.writer.write_all(&buf).map_err(Into::into)
+            self.write_all(&buf)
         }
     }
 
     fn serialize_str(self, v: &str) -> Result<Self::Ok> {
-        if !v.is_ascii() {
+        if core::mem::take(&mut self.wide) {
+            self.write_wstring(v)
+        } else if self.ascii_only && !v.is_ascii() {
             Err(Error::InvalidString(v.into()))
         } else {
             let terminating_char = [0u8];
             let l = v.len() + terminating_char.len();
             self.write_usize_as_u32(l)?;
             self.add_pos(l as u64);
-            self.writer.write_all(v.as_bytes())?;
-            self.writer.write_all(&terminating_char).map_err(Into::into)
+            self.write_all(v.as_bytes())?;
+            self.write_all(&terminating_char)
         }
     }
 
@@ -154,18 +645,46 @@ where
         let l = v.len();
         self.write_usize_as_u32(l)?;
         self.add_pos(l as u64);
-        self.writer.write_all(v).map_err(Into::into)
+        self.write_all(v)
     }
 
     fn serialize_none(self) -> Result<Self::Ok> {
-        Err(Error::TypeNotSupported)
+        let id = match core::mem::replace(&mut self.optional, Optional::Value) {
+            Optional::Parameter => {
+                self.optional = Optional::Absent;
+                return Ok(());
+            }
+            Optional::Member(id) if self.version == CdrVersion::Xcdr1 => id,
+            Optional::Member(_) | Optional::Value => return self.serialize_bool(false),
+            Optional::Absent => return Err(Error::TypeNotSupported),
+        };
+        self.set_pos_of::<u32>()?;
+        self.write_parameter(id, false, &[])
     }
 
-    fn serialize_some<T>(self, _v: &T) -> Result<Self::Ok>
+    fn serialize_some<T>(self, value: &T) -> Result<Self::Ok>
     where
         T: ser::Serialize + ?Sized,
     {
-        Err(Error::TypeNotSupported)
+        let id = match core::mem::replace(&mut self.optional, Optional::Value) {
+            Optional::Parameter => return self.serialize_nested(value),
+            Optional::Member(id) if self.version == CdrVersion::Xcdr1 => id,
+            Optional::Member(_) | Optional::Value => {
+                self.serialize_bool(true)?;
+                return self.serialize_nested(value);
+            }
+            Optional::Absent => return Err(Error::TypeNotSupported),
+        };
+        self.set_pos_of::<u32>()?;
+        self.buffers.push(Vec::new());
+        let result = self.serialize_nested(value);
+        let buffer = self.pop_buffer("unbalanced optional");
+        result?;
+        if buffer.is_empty() {
+            // Would read back as an absent member.
+            return Err(Error::TypeNotSupported);
+        }
+        self.write_parameter(id, false, &buffer)
     }
 
     fn serialize_unit(self) -> Result<Self::Ok> {
@@ -185,11 +704,35 @@ where
         self.serialize_u32(variant_index)
     }
 
-    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<Self::Ok>
+    fn serialize_newtype_struct<T>(self, name: &'static str, value: &T) -> Result<Self::Ok>
     where
         T: ser::Serialize + ?Sized,
     {
-        value.serialize(self)
+        if name == ENCAPSULATED_NAME {
+            return self.write_encapsulated(value);
+        }
+        if name == DELIMITED_NAME {
+            self.delimit = self.version == CdrVersion::Xcdr2;
+            self.serialize_nested(value)?;
+            return self.check_unwrapped();
+        }
+        if let Some(extensibility) = extensibility_of(name) {
+            self.annotated = Some(extensibility);
+            self.serialize_nested(value)?;
+            return self.check_unwrapped();
+        }
+
+        if let Some(bulk) = Bulk::of(name) {
+            return value.serialize(BulkWriter::new(self, bulk));
+        }
+        if name == KEY_NAME && self.optional == Optional::Parameter {
+            self.key = true;
+        }
+
+        self.wide = name == WCHAR_NAME || name == WSTRING_NAME;
+        let result = self.serialize_nested(value);
+        self.wide = false;
+        result
     }
 
     fn serialize_newtype_variant<T>(
@@ -203,17 +746,33 @@ where
         T: ser::Serialize + ?Sized,
     {
         self.serialize_u32(variant_index)?;
-        value.serialize(self)
+        let outermost = self.enter_aggregate();
+        let result = self.serialize_nested(value);
+        self.leave_aggregate(outermost);
+        result
     }
 
     fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq> {
+        self.enter()?;
         let len = len.ok_or(Error::SequenceMustHaveLength)?;
+        self.optional = Optional::Value;
+        let delimited = self.begin_collection()?;
         self.write_usize_as_u32(len)?;
-        Ok(Compound { ser: self })
+        Ok(Compound {
+            delimited,
+            ..Compound::new(self)
+        })
     }
 
     fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple> {
-        Ok(Compound { ser: self })
+        self.enter()?;
+        // Elements of arrays and tuples are not struct members.
+        self.optional = Optional::Value;
+        let delimited = self.begin_collection()?;
+        Ok(Compound {
+            delimited,
+            ..Compound::new(self)
+        })
     }
 
     fn serialize_tuple_struct(
@@ -221,7 +780,10 @@ where
         _name: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeTupleStruct> {
-        Ok(Compound { ser: self })
+        self.enter()?;
+        self.check_unwrapped()?;
+        self.optional = Optional::Value;
+        Ok(Compound::new(self))
     }
 
     fn serialize_tuple_variant(
@@ -231,16 +793,44 @@ where
         _variant: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeTupleVariant> {
+        self.enter()?;
         self.serialize_u32(variant_index)?;
-        Ok(Compound { ser: self })
+        let outermost = self.enter_aggregate();
+        Ok(Compound {
+            outermost,
+            ..Compound::new(self)
+        })
     }
 
-    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
-        Err(Error::TypeNotSupported)
+    fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap> {
+        self.enter()?;
+        let len = len.ok_or(Error::SequenceMustHaveLength)?;
+        self.optional = Optional::Value;
+        let delimited = self.begin_collection()?;
+        self.write_usize_as_u32(len)?;
+        Ok(Compound {
+            delimited,
+            ..Compound::new(self)
+        })
     }
 
     fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeStruct> {
-        Ok(Compound { ser: self })
+        self.enter()?;
+        let annotated = self.annotated.take();
+        self.check_unwrapped()?;
+        let extensibility = self.extensibility.of_struct(annotated, self.in_aggregate);
+        let delimited = self.version == CdrVersion::Xcdr2 && extensibility != Extensibility::Final;
+        if delimited {
+            self.begin_delimited()?;
+        }
+        let outermost = self.enter_aggregate();
+        Ok(Compound {
+            delimited,
+            member_ids: Some(MemberIds::default()),
+            extensibility,
+            outermost,
+            ..Compound::new(self)
+        })
     }
 
     fn serialize_struct_variant(
@@ -250,8 +840,13 @@ where
         _variant: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeStructVariant> {
+        self.enter()?;
         self.serialize_u32(variant_index)?;
-        Ok(Compound { ser: self })
+        let outermost = self.enter_aggregate();
+        Ok(Compound {
+            outermost,
+            ..Compound::new(self)
+        })
     }
 
     fn is_human_readable(&self) -> bool {
@@ -262,11 +857,43 @@ where
 #[doc(hidden)]
 pub struct Compound<'a, W: 'a, E: 'a> {
     ser: &'a mut Serializer<W, E>,
+    delimited: bool,
+    member_ids: Option<MemberIds>,
+    /// Extensibility kind of a struct.
+    extensibility: Extensibility,
+    /// Whether this is the outermost struct or enum.
+    outermost: bool,
+}
+
+impl<'a, W, E> Compound<'a, W, E>
+where
+    W: CdrWrite,
+    E: ByteOrder,
+{
+    fn new(ser: &'a mut Serializer<W, E>) -> Self {
+        Compound {
+            ser,
+            delimited: false,
+            member_ids: None,
+            extensibility: Extensibility::Final,
+            outermost: false,
+        }
+    }
+
+    /// Ends a sequence, an array, a tuple or a map.
+    fn end_collection(self) -> Result<()> {
+        self.ser.leave();
+        if self.delimited {
+            self.ser.end_delimited()
+        } else {
+            Ok(())
+        }
+    }
 }
 
 impl<'a, W, E> ser::SerializeSeq for Compound<'a, W, E>
 where
-    W: Write,
+    W: CdrWrite,
     E: ByteOrder,
 {
     type Error = Error;
@@ -282,13 +909,13 @@ where
 
     #[inline]
     fn end(self) -> Result<()> {
-        Ok(())
+        self.end_collection()
     }
 }
 
 impl<'a, W, E> ser::SerializeTuple for Compound<'a, W, E>
 where
-    W: Write,
+    W: CdrWrite,
     E: ByteOrder,
 {
     type Error = Error;
@@ -304,13 +931,13 @@ where
 
     #[inline]
     fn end(self) -> Result<()> {
-        Ok(())
+        self.end_collection()
     }
 }
 
 impl<'a, W, E> ser::SerializeTupleStruct for Compound<'a, W, E>
 where
-    W: Write,
+    W: CdrWrite,
     E: ByteOrder,
 {
     type Error = Error;
@@ -326,13 +953,13 @@ where
 
     #[inline]
     fn end(self) -> Result<()> {
-        Ok(())
+        self.end_collection()
     }
 }
 
 impl<'a, W, E> ser::SerializeTupleVariant for Compound<'a, W, E>
 where
-    W: Write,
+    W: CdrWrite,
     E: ByteOrder,
 {
     type Error = Error;
@@ -348,13 +975,15 @@ where
 
     #[inline]
     fn end(self) -> Result<()> {
+        self.ser.leave();
+        self.ser.leave_aggregate(self.outermost);
         Ok(())
     }
 }
 
 impl<'a, W, E> ser::SerializeMap for Compound<'a, W, E>
 where
-    W: Write,
+    W: CdrWrite,
     E: ByteOrder,
 {
     type Error = Error;
@@ -378,35 +1007,65 @@ where
 
     #[inline]
     fn end(self) -> Result<()> {
-        Ok(())
+        self.end_collection()
     }
 }
 
 impl<'a, W, E> ser::SerializeStruct for Compound<'a, W, E>
 where
-    W: Write,
+    W: CdrWrite,
     E: ByteOrder,
 {
     type Error = Error;
     type Ok = ();
 
     #[inline]
-    fn serialize_field<T>(&mut self, _key: &'static str, value: &T) -> Result<()>
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
     where
         T: ser::Serialize + ?Sized,
     {
-        value.serialize(&mut *self.ser)
+        let id = match self.member_ids.as_mut() {
+            Some(ids) => ids.next(key),
+            None => return value.serialize(&mut *self.ser),
+        };
+        if self.extensibility != Extensibility::Mutable {
+            self.ser.optional = Optional::Member(id);
+            let result = value.serialize(&mut *self.ser);
+            self.ser.optional = Optional::Value;
+            return result;
+        }
+        match self.ser.version {
+            CdrVersion::Xcdr1 => self.ser.serialize_parameter(id, value),
+            CdrVersion::Xcdr2 => self.ser.serialize_member(id, value),
+        }
     }
 
     #[inline]
-    fn end(self) -> Result<()> {
+    fn skip_field(&mut self, key: &'static str) -> Result<()> {
+        if let Some(ids) = self.member_ids.as_mut() {
+            ids.next(key);
+        }
         Ok(())
     }
+
+    #[inline]
+    fn end(self) -> Result<()> {
+        self.ser.leave();
+        self.ser.leave_aggregate(self.outermost);
+        if self.extensibility == Extensibility::Mutable && self.ser.version == CdrVersion::Xcdr1 {
+            self.ser.write_sentinel()?;
+        }
+        if self.delimited {
+            self.ser.end_delimited()
+        } else {
+            Ok(())
+        }
+    }
 }
 
 impl<'a, W, E> ser::SerializeStructVariant for Compound<'a, W, E>
 where
-    W: Write,
+    W: CdrWrite,
     E: ByteOrder,
 {
     type Error = Error;
@@ -422,46 +1081,131 @@ where
 
     #[inline]
     fn end(self) -> Result<()> {
+        self.ser.leave();
+        self.ser.leave_aggregate(self.outermost);
         Ok(())
     }
 }
 
 /// Serializes a serializable object into a `Vec` of bytes.
+///
+/// See [Options](crate#options) for the optional members that cannot be
+/// written.
 pub fn serialize_data<T, S, E>(value: &T, size_limit: S) -> Result<Vec<u8>>
 where
     T: ser::Serialize + ?Sized,
     S: SizeLimit,
     E: ByteOrder,
 {
-    let mut writer = match size_limit.limit() {
-        Some(limit) => {
-            let actual_size = calc_serialized_data_size_bounded(value, limit)?;
-            Vec::with_capacity(actual_size as usize)
-        }
-        None => {
-            let size = calc_serialized_data_size(value) as usize;
-            Vec::with_capacity(size)
-        }
-    };
+    let mut writer = Vec::new();
+    serialize_data_into_vec::<_, _, E>(&mut writer, value, size_limit)?;
+    Ok(writer)
+}
+
+/// Serializes a serializable object into a `Vec` of bytes without an
+/// encapsulation header, with the byte order, version and extensibility of
+/// the encapsulation given at runtime.
+///
+/// See [Options](crate#options) for the optional members that cannot be
+/// written.
+pub fn serialize_data_with<T, S>(
+    value: &T,
+    kind: EncapsulationKind,
+    size_limit: S,
+) -> Result<Vec<u8>>
+where
+    T: ser::Serialize + ?Sized,
+    S: SizeLimit,
+{
+    match kind.endianness() {
+        Endianness::Big => serialize_data_as::<_, _, BigEndian>(value, kind, size_limit),
+        Endianness::Little => serialize_data_as::<_, _, LittleEndian>(value, kind, size_limit),
+    }
+}
 
-    serialize_data_into::<_, _, _, E>(&mut writer, value, Infinite)?;
+fn serialize_data_as<T, S, E>(value: &T, kind: EncapsulationKind, size_limit: S) -> Result<Vec<u8>>
+where
+    T: ser::Serialize + ?Sized,
+    S: SizeLimit,
+    E: ByteOrder,
+{
+    let mut writer = Vec::new();
+    let mut serializer = Serializer::<_, E>::new(&mut writer)
+        .with_version(kind.version())
+        .with_extensibility(kind.extensibility())
+        .with_size_limit(size_limit);
+    ser::Serialize::serialize(value, &mut serializer)?;
     Ok(writer)
 }
 
-/// Serializes an object directly into a `Write`.
-pub fn serialize_data_into<W, T, S, E>(writer: W, value: &T, size_limit: S) -> Result<()>
+/// Serializes an object into `buf` in place of its contents, reusing its
+/// capacity. `buf` is left empty if the value fails to serialize.
+pub fn serialize_data_into_vec<T, S, E>(buf: &mut Vec<u8>, value: &T, size_limit: S) -> Result<()>
 where
-    W: Write,
     T: ser::Serialize + ?Sized,
     S: SizeLimit,
     E: ByteOrder,
 {
-    if let Some(limit) = size_limit.limit() {
-        calc_serialized_data_size_bounded(value, limit)?;
+    buf.clear();
+    let mut serializer = Serializer::<_, E>::new(&mut *buf).with_size_limit(size_limit);
+    let result = ser::Serialize::serialize(value, &mut serializer);
+    if result.is_err() {
+        buf.clear();
     }
+    result
+}
 
-    let mut serializer = Serializer::<_, E>::new(writer);
-    ser::Serialize::serialize(value, &mut serializer)
+/// Serializes an object directly into a `CdrWrite`.
+///
+/// Under a bounded size limit the value is serialized into a buffer first, so
+/// that nothing is written if it exceeds the limit.
+pub fn serialize_data_into<W, T, S, E>(mut writer: W, value: &T, size_limit: S) -> Result<()>
+where
+    W: CdrWrite,
+    T: ser::Serialize + ?Sized,
+    S: SizeLimit,
+    E: ByteOrder,
+{
+    if size_limit.limit().is_none() {
+        let mut serializer = Serializer::<_, E>::new(writer);
+        return ser::Serialize::serialize(value, &mut serializer);
+    }
+
+    let mut buf = Vec::new();
+    serialize_data_into_vec::<_, _, E>(&mut buf, value, size_limit)?;
+    writer.write_bytes(&buf)
+}
+
+/// Serializes an object into `buf`, returning the number of bytes written.
+///
+/// If `buf` is too small, what fitted is left in it and the value is measured
+/// to fail with `Error::BufferTooSmall`.
+pub fn serialize_data_into_slice<T, S, E>(buf: &mut [u8], value: &T, size_limit: S) -> Result<usize>
+where
+    T: ser::Serialize + ?Sized,
+    S: SizeLimit,
+    E: ByteOrder,
+{
+    let len = buf.len();
+    let limit = size_limit.limit();
+    let mut writer = &mut *buf;
+    let mut serializer = Serializer::<_, E>::new(&mut writer).with_size_limit(size_limit);
+    match ser::Serialize::serialize(value, &mut serializer) {
+        Ok(()) => Ok(len - writer.len()),
+        Err(e) if e.is_write_zero() => {
+            let needed = match limit {
+                Some(limit) => calc_serialized_data_size_bounded(value, limit)?,
+                None => calc_serialized_data_size_with(
+                    value,
+                    CdrVersion::Xcdr1,
+                    Extensibility::Final,
+                    usize::MAX,
+                )?,
+            };
+            Err(Error::BufferTooSmall { needed })
+        }
+        Err(e) => Err(e),
+    }
 }
 
 #[cfg(test)]
@@ -469,6 +1213,7 @@ mod tests {
     use byteorder::{BigEndian, LittleEndian};
 
     use super::*;
+    use crate::size::Infinite;
 
     #[test]
     fn serialize_octet() {
@@ -583,7 +1328,7 @@ mod tests {
 
     #[test]
     fn serialize_float() {
-        let v = std::f32::MIN_POSITIVE;
+        let v = f32::MIN_POSITIVE;
         assert_eq!(
             serialize_data::<_, _, BigEndian>(&v, Infinite).unwrap(),
             vec![0x00, 0x80, 0x00, 0x00]
@@ -596,7 +1341,7 @@ mod tests {
 
     #[test]
     fn serialize_double() {
-        let v = std::f64::MIN_POSITIVE;
+        let v = f64::MIN_POSITIVE;
         assert_eq!(
             serialize_data::<_, _, BigEndian>(&v, Infinite).unwrap(),
             vec![0x00, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]
@@ -642,10 +1387,15 @@ mod tests {
     }
 
     #[test]
-    fn serialize_wstring() {
-        let v = "みなさんこんにちは。これはテストです。";
-        assert!(serialize_data::<_, _, BigEndian>(&v, Infinite).is_err());
-        assert!(serialize_data::<_, _, LittleEndian>(&v, Infinite).is_err());
+    fn serialize_utf8_string() {
+        let v = "みなさんこんにちは。";
+        let encoded = serialize_data::<_, _, BigEndian>(&v, Infinite).unwrap();
+        assert_eq!(&encoded[..4], &[0x00, 0x00, 0x00, 0x1f]);
+        assert_eq!(&encoded[4..34], v.as_bytes());
+        assert_eq!(encoded[34], 0x00);
+
+        let mut serializer = Serializer::<_, LittleEndian>::new(Vec::new()).with_ascii_only(true);
+        assert!(ser::Serialize::serialize(v, &mut serializer).is_err());
     }
 
     #[test]
@@ -863,7 +1613,7 @@ mod tests {
 
     #[test]
     fn serialize_float_array() {
-        let f = std::f32::MIN_POSITIVE;
+        let f = f32::MIN_POSITIVE;
 
         let v = [f, f + 1., f + 2., f + 3., f + 4.];
         assert_eq!(
@@ -890,7 +1640,7 @@ mod tests {
 
     #[test]
     fn serialize_double_array() {
-        let f = std::f64::MIN_POSITIVE;
+        let f = f64::MIN_POSITIVE;
 
         let v = [f, f + 1., f + 2., f + 3., f + 4.];
         assert_eq!(
@@ -1199,7 +1949,7 @@ mod tests {
 
     #[test]
     fn serialize_float_sequence() {
-        let f = std::f32::MIN_POSITIVE;
+        let f = f32::MIN_POSITIVE;
 
         let v = vec![f, f + 1., f + 2., f + 3., f + 4.];
         assert_eq!(
@@ -1228,7 +1978,7 @@ mod tests {
 
     #[test]
     fn serialize_double_sequence() {
-        let f = std::f64::MIN_POSITIVE;
+        let f = f64::MIN_POSITIVE;
 
         let v = vec![f, f + 1., f + 2., f + 3., f + 4.];
         assert_eq!(
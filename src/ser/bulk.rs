@@ -0,0 +1,359 @@
+//! Bulk writing of `Primitives` and `PrimitiveArray`.
+//!
+//! Serde hands their elements over one at a time, so they are gathered in the
+//! byte order of the host into a chunk on the stack, which is written at once
+//! after swapping its bytes in bulk if the byte order differs.
+
+use byteorder::ByteOrder;
+use serde::ser::{self, Impossible};
+
+use super::{CdrWrite, Serializer};
+use crate::{
+    error::{Error, Result},
+    parameter::Optional,
+    primitive::{is_native, swap_bytes, Bulk},
+};
+
+/// Rejects the values that cannot be an element, or hold the elements, of a
+/// bulk sequence or array.
+macro_rules! reject {
+    ($($method:ident($($arg:ident: $ty:ty),*) -> $ret:ty;)*) => {
+        $(
+            fn $method(self, $($arg: $ty),*) -> Result<$ret> {
+                Err(Error::TypeNotSupported)
+            }
+        )*
+    };
+}
+
+/// Values gathered in the byte order of the host, each of `size` bytes.
+struct Chunk {
+    size: usize,
+    buf: [u8; 256],
+    len: usize,
+}
+
+impl Chunk {
+    fn push(&mut self, v: &[u8]) -> Result<()> {
+        if v.len() != self.size {
+            return Err(Error::TypeNotSupported);
+        }
+        self.buf[self.len..self.len + v.len()].copy_from_slice(v);
+        self.len += v.len();
+        Ok(())
+    }
+
+    fn is_full(&self) -> bool {
+        self.len == self.buf.len()
+    }
+}
+
+/// Writes the elements of a bulk sequence or array with a single alignment.
+pub(super) struct BulkWriter<'a, W, E> {
+    ser: &'a mut Serializer<W, E>,
+    bulk: Bulk,
+    chunk: Chunk,
+}
+
+impl<'a, W, E> BulkWriter<'a, W, E>
+where
+    W: CdrWrite,
+    E: ByteOrder,
+{
+    pub(super) fn new(ser: &'a mut Serializer<W, E>, bulk: Bulk) -> Self {
+        BulkWriter {
+            ser,
+            bulk,
+            chunk: Chunk {
+                size: bulk.size(),
+                buf: [0; 256],
+                len: 0,
+            },
+        }
+    }
+
+    /// Writes the number of elements of a sequence and the alignment of the
+    /// first element.
+    fn begin(self, len: usize) -> Result<Self> {
+        self.ser.enter()?;
+        self.ser.optional = Optional::Value;
+        if let Bulk::Sequence(_) = self.bulk {
+            self.ser.write_usize_as_u32(len)?;
+        }
+        if len > 0 {
+            self.ser.write_padding_for(self.chunk.size)?;
+        }
+        Ok(self)
+    }
+
+    fn push_element<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ser::Serialize + ?Sized,
+    {
+        value.serialize(&mut self.chunk)?;
+        if self.chunk.is_full() {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Writes the elements gathered so far in the byte order `E`.
+    fn flush(&mut self) -> Result<()> {
+        let buf = &mut self.chunk.buf[..self.chunk.len];
+        if !is_native::<E>() {
+            swap_bytes(buf, self.chunk.size);
+        }
+        self.ser.add_pos(buf.len() as u64);
+        self.ser.write_all(buf)?;
+        self.chunk.len = 0;
+        Ok(())
+    }
+
+    fn end(mut self) -> Result<()> {
+        self.flush()?;
+        self.ser.leave();
+        Ok(())
+    }
+}
+
+impl<'a, W, E> ser::Serializer for BulkWriter<'a, W, E>
+where
+    W: CdrWrite,
+    E: ByteOrder,
+{
+    type Error = Error;
+    type Ok = ();
+    type SerializeMap = Impossible<(), Error>;
+    type SerializeSeq = Self;
+    type SerializeStruct = Impossible<(), Error>;
+    type SerializeStructVariant = Impossible<(), Error>;
+    type SerializeTuple = Self;
+    type SerializeTupleStruct = Impossible<(), Error>;
+    type SerializeTupleVariant = Impossible<(), Error>;
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self> {
+        match self.bulk {
+            Bulk::Sequence(_) => self.begin(len.ok_or(Error::SequenceMustHaveLength)?),
+            Bulk::Array(_) => Err(Error::TypeNotSupported),
+        }
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self> {
+        match self.bulk {
+            Bulk::Array(_) => self.begin(len),
+            Bulk::Sequence(_) => Err(Error::TypeNotSupported),
+        }
+    }
+
+    reject! {
+        serialize_bool(_v: bool) -> ();
+        serialize_i8(_v: i8) -> ();
+        serialize_i16(_v: i16) -> ();
+        serialize_i32(_v: i32) -> ();
+        serialize_i64(_v: i64) -> ();
+        serialize_u8(_v: u8) -> ();
+        serialize_u16(_v: u16) -> ();
+        serialize_u32(_v: u32) -> ();
+        serialize_u64(_v: u64) -> ();
+        serialize_f32(_v: f32) -> ();
+        serialize_f64(_v: f64) -> ();
+        serialize_char(_v: char) -> ();
+        serialize_str(_v: &str) -> ();
+        serialize_bytes(_v: &[u8]) -> ();
+        serialize_none() -> ();
+        serialize_unit() -> ();
+        serialize_unit_struct(_name: &'static str) -> ();
+        serialize_unit_variant(_name: &'static str, _index: u32, _variant: &'static str) -> ();
+        serialize_tuple_struct(_name: &'static str, _len: usize) -> Self::SerializeTupleStruct;
+        serialize_tuple_variant(
+            _name: &'static str,
+            _index: u32,
+            _variant: &'static str,
+            _len: usize
+        ) -> Self::SerializeTupleVariant;
+        serialize_map(_len: Option<usize>) -> Self::SerializeMap;
+        serialize_struct(_name: &'static str, _len: usize) -> Self::SerializeStruct;
+        serialize_struct_variant(
+            _name: &'static str,
+            _index: u32,
+            _variant: &'static str,
+            _len: usize
+        ) -> Self::SerializeStructVariant;
+    }
+
+    fn serialize_some<T>(self, _value: &T) -> Result<()>
+    where
+        T: ser::Serialize + ?Sized,
+    {
+        Err(Error::TypeNotSupported)
+    }
+
+    fn serialize_newtype_struct<T>(self, _name: &'static str, _value: &T) -> Result<()>
+    where
+        T: ser::Serialize + ?Sized,
+    {
+        Err(Error::TypeNotSupported)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<()>
+    where
+        T: ser::Serialize + ?Sized,
+    {
+        Err(Error::TypeNotSupported)
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+impl<'a, W, E> ser::SerializeSeq for BulkWriter<'a, W, E>
+where
+    W: CdrWrite,
+    E: ByteOrder,
+{
+    type Error = Error;
+    type Ok = ();
+
+    #[inline]
+    fn serialize_element<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ser::Serialize + ?Sized,
+    {
+        self.push_element(value)
+    }
+
+    #[inline]
+    fn end(self) -> Result<()> {
+        BulkWriter::end(self)
+    }
+}
+
+impl<'a, W, E> ser::SerializeTuple for BulkWriter<'a, W, E>
+where
+    W: CdrWrite,
+    E: ByteOrder,
+{
+    type Error = Error;
+    type Ok = ();
+
+    #[inline]
+    fn serialize_element<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ser::Serialize + ?Sized,
+    {
+        self.push_element(value)
+    }
+
+    #[inline]
+    fn end(self) -> Result<()> {
+        BulkWriter::end(self)
+    }
+}
+
+macro_rules! impl_push_value {
+    ($ser_method:ident($ty:ty)) => {
+        #[inline]
+        fn $ser_method(self, v: $ty) -> Result<()> {
+            self.push(&v.to_ne_bytes())
+        }
+    };
+}
+
+/// Takes a single element.
+impl ser::Serializer for &mut Chunk {
+    type Error = Error;
+    type Ok = ();
+    type SerializeMap = Impossible<(), Error>;
+    type SerializeSeq = Impossible<(), Error>;
+    type SerializeStruct = Impossible<(), Error>;
+    type SerializeStructVariant = Impossible<(), Error>;
+    type SerializeTuple = Impossible<(), Error>;
+    type SerializeTupleStruct = Impossible<(), Error>;
+    type SerializeTupleVariant = Impossible<(), Error>;
+
+    impl_push_value! { serialize_i8(i8) }
+
+    impl_push_value! { serialize_i16(i16) }
+
+    impl_push_value! { serialize_i32(i32) }
+
+    impl_push_value! { serialize_i64(i64) }
+
+    impl_push_value! { serialize_u8(u8) }
+
+    impl_push_value! { serialize_u16(u16) }
+
+    impl_push_value! { serialize_u32(u32) }
+
+    impl_push_value! { serialize_u64(u64) }
+
+    impl_push_value! { serialize_f32(f32) }
+
+    impl_push_value! { serialize_f64(f64) }
+
+    reject! {
+        serialize_bool(_v: bool) -> ();
+        serialize_char(_v: char) -> ();
+        serialize_str(_v: &str) -> ();
+        serialize_bytes(_v: &[u8]) -> ();
+        serialize_none() -> ();
+        serialize_unit() -> ();
+        serialize_unit_struct(_name: &'static str) -> ();
+        serialize_unit_variant(_name: &'static str, _index: u32, _variant: &'static str) -> ();
+        serialize_seq(_len: Option<usize>) -> Self::SerializeSeq;
+        serialize_tuple(_len: usize) -> Self::SerializeTuple;
+        serialize_tuple_struct(_name: &'static str, _len: usize) -> Self::SerializeTupleStruct;
+        serialize_tuple_variant(
+            _name: &'static str,
+            _index: u32,
+            _variant: &'static str,
+            _len: usize
+        ) -> Self::SerializeTupleVariant;
+        serialize_map(_len: Option<usize>) -> Self::SerializeMap;
+        serialize_struct(_name: &'static str, _len: usize) -> Self::SerializeStruct;
+        serialize_struct_variant(
+            _name: &'static str,
+            _index: u32,
+            _variant: &'static str,
+            _len: usize
+        ) -> Self::SerializeStructVariant;
+    }
+
+    fn serialize_some<T>(self, _value: &T) -> Result<()>
+    where
+        T: ser::Serialize + ?Sized,
+    {
+        Err(Error::TypeNotSupported)
+    }
+
+    fn serialize_newtype_struct<T>(self, _name: &'static str, _value: &T) -> Result<()>
+    where
+        T: ser::Serialize + ?Sized,
+    {
+        Err(Error::TypeNotSupported)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<()>
+    where
+        T: ser::Serialize + ?Sized,
+    {
+        Err(Error::TypeNotSupported)
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
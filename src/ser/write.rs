@@ -0,0 +1,78 @@
+//! Sinks of bytes for the serializer.
+
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::io;
+
+#[cfg(not(feature = "std"))]
+use crate::error::Error;
+use crate::error::Result;
+
+/// A sink of bytes for the `Serializer`.
+///
+/// With the `std` feature it is implemented for every `std::io::Write`.
+/// Without it, it is implemented for `Vec<u8>`, which grows as needed, and for
+/// `&mut [u8]`, which is advanced past the bytes written like its
+/// `std::io::Write` counterpart.
+pub trait CdrWrite {
+    /// Writes all of `buf`.
+    fn write_bytes(&mut self, buf: &[u8]) -> Result<()>;
+}
+
+#[cfg(feature = "std")]
+impl<W> CdrWrite for W
+where
+    W: io::Write + ?Sized,
+{
+    fn write_bytes(&mut self, buf: &[u8]) -> Result<()> {
+        self.write_all(buf).map_err(Into::into)
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl CdrWrite for Vec<u8> {
+    fn write_bytes(&mut self, buf: &[u8]) -> Result<()> {
+        self.extend_from_slice(buf);
+        Ok(())
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl CdrWrite for &mut [u8] {
+    fn write_bytes(&mut self, buf: &[u8]) -> Result<()> {
+        if buf.len() > self.len() {
+            return Err(Error::WriteZero);
+        }
+
+        let (written, rest) = core::mem::take(self).split_at_mut(buf.len());
+        written.copy_from_slice(buf);
+        *self = rest;
+        Ok(())
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl<W> CdrWrite for &mut W
+where
+    W: CdrWrite + ?Sized,
+{
+    fn write_bytes(&mut self, buf: &[u8]) -> Result<()> {
+        (**self).write_bytes(buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slice_writer() {
+        let mut buf = [0; 4];
+        let mut writer = &mut buf[..];
+        writer.write_bytes(&[1, 2, 3]).unwrap();
+        assert_eq!(writer.len(), 1);
+        assert!(writer.write_bytes(&[4, 5]).is_err());
+        assert_eq!(buf[..3], [1, 2, 3]);
+    }
+}
@@ -2,7 +2,21 @@
 
 use serde::ser;
 
-use crate::error::{Error, Result};
+use crate::{
+    encapsulated::ENCAPSULATED_NAME,
+    encapsulation::{CdrVersion, Extensibility},
+    error::{Error, Result},
+    parameter::{
+        Lead, MemberIds, Optional, EMHEADER_ID_MASK, LC_NEXTINT, PID_EXTENDED_ID_MASK,
+        PID_SHORT_ID_LIMIT,
+    },
+    wide::{encode_wchar, WideEncoding, WCHAR_NAME, WSTRING_NAME},
+    xtypes::{extensibility_of, DELIMITED_NAME},
+};
+
+/// Default for the deepest nesting of compound values, options and newtypes
+/// that can be read. Writing is not limited unless asked for.
+pub const DEFAULT_MAX_DEPTH: usize = 256;
 
 /// Limits on the number of bytes that can be read or written.
 pub trait SizeLimit {
@@ -72,41 +86,259 @@ impl SizeLimit for Counter {
 struct SizeChecker<S> {
     counter: S,
     pos: usize,
+    version: CdrVersion,
+    extensibility: Extensibility,
+    /// Whether a struct or an enum is being counted, whose nested structs are
+    /// final unless annotated otherwise.
+    in_aggregate: bool,
+    lead: Lead,
+    optional: Optional,
+    wide_encoding: WideEncoding,
+    wide: bool,
+    /// Whether the next sequence, array or map is preceded by a DHEADER, as
+    /// `Delimited` asks.
+    delimit: bool,
+    /// Extensibility kind of the next struct, as `Final`, `Appendable` or
+    /// `Mutable` gives it.
+    annotated: Option<Extensibility>,
+    depth: usize,
+    max_depth: usize,
 }
 
 impl<S> SizeChecker<S>
 where
     S: SizeLimit,
 {
-    fn add_padding_of<T>(&mut self) -> Result<()> {
-        let alignment = std::mem::size_of::<T>();
+    fn new(
+        counter: S,
+        version: CdrVersion,
+        extensibility: Extensibility,
+        max_depth: usize,
+    ) -> Self {
+        Self {
+            counter,
+            pos: 0,
+            version,
+            extensibility,
+            in_aggregate: false,
+            lead: Lead::Other,
+            optional: Optional::Value,
+            wide_encoding: WideEncoding::default(),
+            wide: false,
+            delimit: false,
+            annotated: None,
+            depth: 0,
+            max_depth,
+        }
+    }
+
+    fn padding_of<T>(&self) -> usize {
+        self.padding_for(core::mem::size_of::<T>())
+    }
+
+    fn padding_for(&self, size: usize) -> usize {
+        let alignment = size.min(self.version.max_alignment());
         let rem_mask = alignment - 1; // mask like 0x0, 0x1, 0x3, 0x7
         match self.pos & rem_mask {
-            0 => Ok(()),
-            n @ 1..=7 => {
-                let amt = alignment - n;
-                self.add_size(amt as u64)
-            }
+            0 => 0,
+            n @ 1..=7 => alignment - n,
             _ => unreachable!(),
         }
     }
 
+    fn add_padding_of<T>(&mut self) -> Result<()> {
+        self.add_padding_for(core::mem::size_of::<T>())
+    }
+
+    fn add_padding_for(&mut self, size: usize) -> Result<()> {
+        match self.padding_for(size) {
+            0 => Ok(()),
+            amt => self.add_size(amt as u64),
+        }
+    }
+
+    /// Enters a compound value, an option or a newtype.
+    fn enter(&mut self) -> Result<()> {
+        if self.depth >= self.max_depth {
+            return Err(Error::RecursionLimitExceeded);
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    fn leave(&mut self) {
+        self.depth -= 1;
+    }
+
+    /// Adds the size of a value nested in an option or a newtype.
+    fn add_nested<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ser::Serialize + ?Sized,
+    {
+        self.enter()?;
+        value.serialize(&mut *self)?;
+        self.leave();
+        Ok(())
+    }
+
     fn add_size(&mut self, size: u64) -> Result<()> {
+        if self.delimit || self.annotated.is_some() {
+            // Only collections can be delimited, and only structs annotated.
+            return Err(Error::TypeNotSupported);
+        }
+        if self.lead == Lead::Start {
+            self.lead = Lead::Other;
+        }
+        if let Optional::Member(_) | Optional::Parameter = self.optional {
+            self.optional = Optional::Value;
+        }
         self.pos += size as usize;
         self.counter.add(size)
     }
 
+    /// Adds the size of `value` written as an octet sequence starting with the
+    /// byte-order flag, with alignment relative to the start of the sequence.
+    fn add_encapsulated<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ser::Serialize + ?Sized,
+    {
+        self.add_value(0_u32)?;
+        let pos = core::mem::replace(&mut self.pos, 0);
+        let result = self.add_size(1).and_then(|()| self.add_nested(value));
+        self.pos += pos;
+        result
+    }
+
     fn add_usize_as_u32(&mut self, v: usize) -> Result<()> {
-        if v > std::u32::MAX as usize {
+        if v > u32::MAX as usize {
             return Err(Error::NumberOutOfRange);
         }
 
-        ser::Serializer::serialize_u32(self, v as u32)
+        let starts_member = self.lead == Lead::Start;
+        ser::Serializer::serialize_u32(&mut *self, v as u32)?;
+        if starts_member {
+            self.lead = Lead::Prefix(v as u32);
+        }
+        Ok(())
+    }
+
+    /// Enters a struct or an enum, returning whether it is the outermost one.
+    fn enter_aggregate(&mut self) -> bool {
+        !core::mem::replace(&mut self.in_aggregate, true)
+    }
+
+    fn leave_aggregate(&mut self, outermost: bool) {
+        if outermost {
+            self.in_aggregate = false;
+        }
+    }
+
+    fn add_delimiter(&mut self) -> Result<()> {
+        let starts_member = self.lead == Lead::Start;
+        self.add_value(0_u32)?;
+        if starts_member {
+            self.lead = Lead::Delimited;
+        }
+        Ok(())
+    }
+
+    /// Begins a sequence, an array or a map, with a DHEADER if `Delimited`
+    /// asks for one.
+    fn begin_collection(&mut self) -> Result<()> {
+        if self.annotated.is_some() {
+            return Err(Error::TypeNotSupported);
+        }
+        if core::mem::take(&mut self.delimit) {
+            self.add_delimiter()?;
+        }
+        Ok(())
+    }
+
+    /// Fails if a `Delimited` wraps something other than a collection, or a
+    /// `Final`, an `Appendable` or a `Mutable` something other than a struct.
+    fn check_unwrapped(&self) -> Result<()> {
+        if self.delimit || self.annotated.is_some() {
+            Err(Error::TypeNotSupported)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Adds the size of a member of a mutable struct after a 4-byte header,
+    /// returning its alignment padding and length, or `None` if it is an
+    /// absent optional member. The padding and header are left for the caller
+    /// to add once the member turns out to be present.
+    fn add_member_value<T>(&mut self, value: &T) -> Result<Option<(usize, usize)>>
+    where
+        T: ser::Serialize + ?Sized,
+    {
+        let start = self.pos;
+        let padding = self.padding_of::<u32>();
+        self.pos += padding + 4;
+        self.optional = Optional::Parameter;
+        let result = value.serialize(&mut *self);
+        let optional = core::mem::replace(&mut self.optional, Optional::Value);
+        result?;
+        if optional == Optional::Absent {
+            self.pos = start;
+            Ok(None)
+        } else {
+            Ok(Some((padding, self.pos - start - padding - 4)))
+        }
+    }
+
+    fn add_parameter<T>(&mut self, id: u32, value: &T) -> Result<()>
+    where
+        T: ser::Serialize + ?Sized,
+    {
+        let (padding, len) = match self.add_member_value(value)? {
+            Some(member) => member,
+            None => return Ok(()),
+        };
+        let trailing = self.padding_of::<u32>();
+        self.add_size(trailing as u64)?;
+        self.counter.add(padding as u64 + 4)?;
+        self.add_parameter_extension(id, len + trailing)
+    }
+
+    /// Adds the size by which a parameter header is extended, if it needs to
+    /// be.
+    fn add_parameter_extension(&mut self, id: u32, len: usize) -> Result<()> {
+        if id < PID_SHORT_ID_LIMIT && len <= u16::MAX as usize {
+            Ok(())
+        } else if id > PID_EXTENDED_ID_MASK {
+            Err(Error::NumberOutOfRange)
+        } else {
+            self.add_size(8)
+        }
+    }
+
+    fn add_member<T>(&mut self, id: u32, value: &T) -> Result<()>
+    where
+        T: ser::Serialize + ?Sized,
+    {
+        if id > EMHEADER_ID_MASK {
+            return Err(Error::NumberOutOfRange);
+        }
+
+        let lead = core::mem::replace(&mut self.lead, Lead::Start);
+        let result = self.add_member_value(value);
+        let member_lead = core::mem::replace(&mut self.lead, lead);
+        let (padding, len) = match result? {
+            Some(member) => member,
+            None => return Ok(()),
+        };
+        self.counter.add(padding as u64 + 4)?;
+        if member_lead.length_code(len as u64) == LC_NEXTINT {
+            self.add_size(4)
+        } else {
+            Ok(())
+        }
     }
 
     fn add_value<T>(&mut self, _v: T) -> Result<()> {
         self.add_padding_of::<T>()?;
-        self.add_size(std::mem::size_of::<T>() as u64)
+        self.add_size(core::mem::size_of::<T>() as u64)
     }
 }
 
@@ -156,29 +388,74 @@ where
         self.add_value(0u8)
     }
 
-    fn serialize_char(self, _v: char) -> Result<Self::Ok> {
-        self.add_size(1)
+    fn serialize_char(self, v: char) -> Result<Self::Ok> {
+        if !core::mem::take(&mut self.wide) {
+            return self.add_size(1);
+        }
+
+        let unit = encode_wchar(v)?;
+        match self.wide_encoding {
+            WideEncoding::Xcdr => self.add_value(unit),
+            WideEncoding::Giop => self.add_size(3), // adds the length octet
+        }
     }
 
     fn serialize_str(self, v: &str) -> Result<Self::Ok> {
-        self.add_value(0_u32)?;
+        if core::mem::take(&mut self.wide) {
+            let mut units = v.encode_utf16().count();
+            match (self.wide_encoding, self.version) {
+                (WideEncoding::Xcdr, CdrVersion::Xcdr1) => {
+                    units += 1; // adds a terminating null code unit
+                    self.add_usize_as_u32(units)?;
+                }
+                _ => self.add_usize_as_u32(units * 2)?,
+            }
+            return self.add_size(units as u64 * 2);
+        }
+
+        self.add_usize_as_u32(v.len() + 1)?;
         self.add_size(v.len() as u64 + 1) // adds the length 1 of a terminating character
     }
 
     fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok> {
-        self.add_value(0_u32)?;
+        self.add_usize_as_u32(v.len())?;
         self.add_size(v.len() as u64)
     }
 
     fn serialize_none(self) -> Result<Self::Ok> {
-        Err(Error::TypeNotSupported)
+        let id = match core::mem::replace(&mut self.optional, Optional::Value) {
+            Optional::Parameter => {
+                self.optional = Optional::Absent;
+                return Ok(());
+            }
+            Optional::Member(id) if self.version == CdrVersion::Xcdr1 => id,
+            Optional::Member(_) | Optional::Value => return self.add_value(0_u8),
+            Optional::Absent => return Err(Error::TypeNotSupported),
+        };
+        self.add_value(0_u32)?;
+        self.add_parameter_extension(id, 0)
     }
 
-    fn serialize_some<T>(self, _v: &T) -> Result<Self::Ok>
+    fn serialize_some<T>(self, value: &T) -> Result<Self::Ok>
     where
         T: ser::Serialize + ?Sized,
     {
-        Err(Error::TypeNotSupported)
+        let id = match core::mem::replace(&mut self.optional, Optional::Value) {
+            Optional::Parameter => return self.add_nested(value),
+            Optional::Member(id) if self.version == CdrVersion::Xcdr1 => id,
+            Optional::Member(_) | Optional::Value => {
+                self.add_value(0_u8)?;
+                return self.add_nested(value);
+            }
+            Optional::Absent => return Err(Error::TypeNotSupported),
+        };
+        self.add_value(0_u32)?;
+        let start = self.pos;
+        self.add_nested(value)?;
+        if self.pos == start {
+            return Err(Error::TypeNotSupported);
+        }
+        self.add_parameter_extension(id, self.pos - start)
     }
 
     fn serialize_unit(self) -> Result<Self::Ok> {
@@ -198,11 +475,28 @@ where
         self.serialize_u32(variant_index)
     }
 
-    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<Self::Ok>
+    fn serialize_newtype_struct<T>(self, name: &'static str, value: &T) -> Result<Self::Ok>
     where
         T: ser::Serialize + ?Sized,
     {
-        value.serialize(self)
+        if name == ENCAPSULATED_NAME {
+            return self.add_encapsulated(value);
+        }
+        if name == DELIMITED_NAME {
+            self.delimit = self.version == CdrVersion::Xcdr2;
+            self.add_nested(value)?;
+            return self.check_unwrapped();
+        }
+        if let Some(extensibility) = extensibility_of(name) {
+            self.annotated = Some(extensibility);
+            self.add_nested(value)?;
+            return self.check_unwrapped();
+        }
+
+        self.wide = name == WCHAR_NAME || name == WSTRING_NAME;
+        let result = self.add_nested(value);
+        self.wide = false;
+        result
     }
 
     fn serialize_newtype_variant<T>(
@@ -216,17 +510,26 @@ where
         T: ser::Serialize + ?Sized,
     {
         self.serialize_u32(variant_index)?;
-        value.serialize(self)
+        let outermost = self.enter_aggregate();
+        let result = self.add_nested(value);
+        self.leave_aggregate(outermost);
+        result
     }
 
     fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq> {
+        self.enter()?;
         let len = len.ok_or(Error::SequenceMustHaveLength)?;
+        self.optional = Optional::Value;
+        self.begin_collection()?;
         self.add_usize_as_u32(len)?;
-        Ok(SizeCompound { ser: self })
+        Ok(SizeCompound::new(self))
     }
 
     fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple> {
-        Ok(SizeCompound { ser: self })
+        self.enter()?;
+        self.optional = Optional::Value;
+        self.begin_collection()?;
+        Ok(SizeCompound::new(self))
     }
 
     fn serialize_tuple_struct(
@@ -234,7 +537,10 @@ where
         _name: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeTupleStruct> {
-        Ok(SizeCompound { ser: self })
+        self.enter()?;
+        self.check_unwrapped()?;
+        self.optional = Optional::Value;
+        Ok(SizeCompound::new(self))
     }
 
     fn serialize_tuple_variant(
@@ -244,16 +550,39 @@ where
         _variant: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeTupleVariant> {
+        self.enter()?;
         self.serialize_u32(variant_index)?;
-        Ok(SizeCompound { ser: self })
+        let outermost = self.enter_aggregate();
+        Ok(SizeCompound {
+            outermost,
+            ..SizeCompound::new(self)
+        })
     }
 
-    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
-        Err(Error::TypeNotSupported)
+    fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap> {
+        self.enter()?;
+        let len = len.ok_or(Error::SequenceMustHaveLength)?;
+        self.optional = Optional::Value;
+        self.begin_collection()?;
+        self.add_usize_as_u32(len)?;
+        Ok(SizeCompound::new(self))
     }
 
     fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeStruct> {
-        Ok(SizeCompound { ser: self })
+        self.enter()?;
+        let annotated = self.annotated.take();
+        self.check_unwrapped()?;
+        let extensibility = self.extensibility.of_struct(annotated, self.in_aggregate);
+        if self.version == CdrVersion::Xcdr2 && extensibility != Extensibility::Final {
+            self.add_delimiter()?;
+        }
+        let outermost = self.enter_aggregate();
+        Ok(SizeCompound {
+            member_ids: Some(MemberIds::default()),
+            extensibility,
+            outermost,
+            ..SizeCompound::new(self)
+        })
     }
 
     fn serialize_struct_variant(
@@ -263,8 +592,13 @@ where
         _variant: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeStructVariant> {
+        self.enter()?;
         self.serialize_u32(variant_index)?;
-        Ok(SizeCompound { ser: self })
+        let outermost = self.enter_aggregate();
+        Ok(SizeCompound {
+            outermost,
+            ..SizeCompound::new(self)
+        })
     }
 
     fn is_human_readable(&self) -> bool {
@@ -275,6 +609,31 @@ where
 #[doc(hidden)]
 pub struct SizeCompound<'a, S: 'a> {
     ser: &'a mut SizeChecker<S>,
+    member_ids: Option<MemberIds>,
+    /// Extensibility kind of a struct.
+    extensibility: Extensibility,
+    /// Whether this is the outermost struct or enum.
+    outermost: bool,
+}
+
+impl<'a, S> SizeCompound<'a, S>
+where
+    S: SizeLimit,
+{
+    fn new(ser: &'a mut SizeChecker<S>) -> Self {
+        SizeCompound {
+            ser,
+            member_ids: None,
+            extensibility: Extensibility::Final,
+            outermost: false,
+        }
+    }
+
+    /// Ends a sequence, an array, a tuple or a map.
+    fn end_collection(self) -> Result<()> {
+        self.ser.leave();
+        Ok(())
+    }
 }
 
 impl<'a, S> ser::SerializeSeq for SizeCompound<'a, S>
@@ -294,7 +653,7 @@ where
 
     #[inline]
     fn end(self) -> Result<()> {
-        Ok(())
+        self.end_collection()
     }
 }
 
@@ -315,7 +674,7 @@ where
 
     #[inline]
     fn end(self) -> Result<()> {
-        Ok(())
+        self.end_collection()
     }
 }
 
@@ -336,7 +695,7 @@ where
 
     #[inline]
     fn end(self) -> Result<()> {
-        Ok(())
+        self.end_collection()
     }
 }
 
@@ -357,6 +716,8 @@ where
 
     #[inline]
     fn end(self) -> Result<()> {
+        self.ser.leave();
+        self.ser.leave_aggregate(self.outermost);
         Ok(())
     }
 }
@@ -386,7 +747,7 @@ where
 
     #[inline]
     fn end(self) -> Result<()> {
-        Ok(())
+        self.end_collection()
     }
 }
 
@@ -398,17 +759,44 @@ where
     type Ok = ();
 
     #[inline]
-    fn serialize_field<T>(&mut self, _key: &'static str, value: &T) -> Result<()>
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
     where
         T: ser::Serialize + ?Sized,
     {
-        value.serialize(&mut *self.ser)
+        let id = match self.member_ids.as_mut() {
+            Some(ids) => ids.next(key),
+            None => return value.serialize(&mut *self.ser),
+        };
+        if self.extensibility != Extensibility::Mutable {
+            self.ser.optional = Optional::Member(id);
+            let result = value.serialize(&mut *self.ser);
+            self.ser.optional = Optional::Value;
+            return result;
+        }
+        match self.ser.version {
+            CdrVersion::Xcdr1 => self.ser.add_parameter(id, value),
+            CdrVersion::Xcdr2 => self.ser.add_member(id, value),
+        }
     }
 
     #[inline]
-    fn end(self) -> Result<()> {
+    fn skip_field(&mut self, key: &'static str) -> Result<()> {
+        if let Some(ids) = self.member_ids.as_mut() {
+            ids.next(key);
+        }
         Ok(())
     }
+
+    #[inline]
+    fn end(self) -> Result<()> {
+        self.ser.leave();
+        self.ser.leave_aggregate(self.outermost);
+        if self.extensibility == Extensibility::Mutable && self.ser.version == CdrVersion::Xcdr1 {
+            self.ser.add_value(0_u32)
+        } else {
+            Ok(())
+        }
+    }
 }
 
 impl<'a, S> ser::SerializeStructVariant for SizeCompound<'a, S>
@@ -428,40 +816,104 @@ where
 
     #[inline]
     fn end(self) -> Result<()> {
+        self.ser.leave();
+        self.ser.leave_aggregate(self.outermost);
         Ok(())
     }
 }
 
-/// Returns the size that an object would be if serialized.
+/// Returns the size that an object would be if serialized as plain CDR.
+///
+/// A value that cannot be serialized has no size, in which case the size of
+/// the data preceding the error is returned. Use
+/// `calc_serialized_data_size_bounded` to learn about the error.
 pub fn calc_serialized_data_size<T>(value: &T) -> u64
 where
     T: ser::Serialize + ?Sized,
 {
-    let mut checker = SizeChecker {
-        counter: Counter {
-            total: 0,
-            limit: None,
-        },
-        pos: 0,
+    let counter = Counter {
+        total: 0,
+        limit: None,
     };
+    let mut checker =
+        SizeChecker::new(counter, CdrVersion::Xcdr1, Extensibility::Final, usize::MAX);
 
     value.serialize(&mut checker).ok();
     checker.counter.total
 }
 
 /// Given a maximum size limit, check how large an object would be if it were
-/// to be serialized.
+/// to be serialized as plain CDR.
 pub fn calc_serialized_data_size_bounded<T>(value: &T, max: u64) -> Result<u64>
 where
     T: ser::Serialize + ?Sized,
 {
-    let mut checker = SizeChecker {
-        counter: Bounded(max),
-        pos: 0,
+    calc_serialized_data_size_bounded_with(
+        value,
+        max,
+        CdrVersion::Xcdr1,
+        Extensibility::Final,
+        usize::MAX,
+    )
+}
+
+/// Returns the size that an object would be if serialized with the given
+/// version, extensibility and maximum nesting depth.
+pub(crate) fn calc_serialized_data_size_with<T>(
+    value: &T,
+    version: CdrVersion,
+    extensibility: Extensibility,
+    max_depth: usize,
+) -> Result<u64>
+where
+    T: ser::Serialize + ?Sized,
+{
+    let counter = Counter {
+        total: 0,
+        limit: None,
     };
+    let mut checker = SizeChecker::new(counter, version, extensibility, max_depth);
+    value.serialize(&mut checker)?;
+    Ok(checker.counter.total)
+}
+
+pub(crate) fn calc_serialized_data_size_bounded_with<T>(
+    value: &T,
+    max: u64,
+    version: CdrVersion,
+    extensibility: Extensibility,
+    max_depth: usize,
+) -> Result<u64>
+where
+    T: ser::Serialize + ?Sized,
+{
+    let mut checker = SizeChecker::new(Bounded(max), version, extensibility, max_depth);
 
     match value.serialize(&mut checker) {
         Ok(_) => Ok(max - checker.counter.0),
         Err(e) => Err(e),
     }
 }
+
+/// Returns the size that an object would be if serialized as plain CDR
+/// starting `pos` bytes after the origin that alignment is relative to, with
+/// wide characters and strings following `wide_encoding`.
+pub(crate) fn calc_serialized_data_size_at<T>(
+    value: &T,
+    pos: u64,
+    wide_encoding: WideEncoding,
+) -> Result<u64>
+where
+    T: ser::Serialize + ?Sized,
+{
+    let counter = Counter {
+        total: 0,
+        limit: None,
+    };
+    let mut checker =
+        SizeChecker::new(counter, CdrVersion::Xcdr1, Extensibility::Final, usize::MAX);
+    checker.pos = pos as usize;
+    checker.wide_encoding = wide_encoding;
+    value.serialize(&mut checker)?;
+    Ok(checker.counter.total)
+}
@@ -0,0 +1,238 @@
+//! Wide characters and strings encoded as UTF-16.
+
+use alloc::{
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use core::{fmt, ops::Deref};
+
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+use serde::{de, ser};
+
+use crate::error::{Error, Result};
+
+pub(crate) const WCHAR_NAME: &str = "$cdr::WChar";
+pub(crate) const WSTRING_NAME: &str = "$cdr::WString";
+
+/// Byte order mark starting UTF-16 text written in big-endian byte order.
+const BOM_BE: [u8; 2] = [0xfe, 0xff];
+
+/// Byte order mark starting UTF-16 text written in little-endian byte order.
+const BOM_LE: [u8; 2] = [0xff, 0xfe];
+
+/// Conventions for encoding IDL `wchar` and `wstring`.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum WideEncoding {
+    /// UTF-16 code units in the byte order of the stream, as defined by
+    /// DDS-XTypes. In XCDR1 a `wstring` is prefixed with its length in code
+    /// units and terminated with a null code unit, while in XCDR2 it is
+    /// prefixed with its length in bytes and has no terminator.
+    Xcdr,
+    /// UTF-16 as negotiated in GIOP 1.2. A `wchar` is prefixed with its length
+    /// in an octet and a `wstring` with its length in bytes, without a
+    /// terminator. Text is written in big-endian byte order, and read in the
+    /// byte order given by a leading byte order mark, if any.
+    Giop,
+}
+
+impl Default for WideEncoding {
+    fn default() -> Self {
+        WideEncoding::Xcdr
+    }
+}
+
+/// An IDL `wchar`, a character encoded as a single UTF-16 code unit.
+///
+/// Only characters of the Basic Multilingual Plane fit in a single code unit.
+/// Others, such as emoji, fail to serialize with `Error::InvalidChar` and
+/// need a `WString`. GIOP code sets other than UTF-16, such as UTF-32, are not
+/// supported.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct WChar(pub char);
+
+impl From<char> for WChar {
+    fn from(c: char) -> Self {
+        WChar(c)
+    }
+}
+
+impl ser::Serialize for WChar {
+    fn serialize<S>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        serializer.serialize_newtype_struct(WCHAR_NAME, &self.0)
+    }
+}
+
+impl<'de> de::Deserialize<'de> for WChar {
+    fn deserialize<D>(deserializer: D) -> core::result::Result<Self, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        struct Visitor;
+
+        impl<'de> de::Visitor<'de> for Visitor {
+            type Value = WChar;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a wide character")
+            }
+
+            fn visit_newtype_struct<D>(
+                self,
+                deserializer: D,
+            ) -> core::result::Result<WChar, D::Error>
+            where
+                D: de::Deserializer<'de>,
+            {
+                de::Deserialize::deserialize(deserializer).map(WChar)
+            }
+        }
+
+        deserializer.deserialize_newtype_struct(WCHAR_NAME, Visitor)
+    }
+}
+
+/// An IDL `wstring`, a string encoded as UTF-16 code units.
+#[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct WString(pub String);
+
+impl From<String> for WString {
+    fn from(s: String) -> Self {
+        WString(s)
+    }
+}
+
+impl From<&str> for WString {
+    fn from(s: &str) -> Self {
+        WString(s.to_string())
+    }
+}
+
+impl Deref for WString {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl ser::Serialize for WString {
+    fn serialize<S>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        serializer.serialize_newtype_struct(WSTRING_NAME, self.0.as_str())
+    }
+}
+
+impl<'de> de::Deserialize<'de> for WString {
+    fn deserialize<D>(deserializer: D) -> core::result::Result<Self, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        struct Visitor;
+
+        impl<'de> de::Visitor<'de> for Visitor {
+            type Value = WString;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a wide string")
+            }
+
+            fn visit_newtype_struct<D>(
+                self,
+                deserializer: D,
+            ) -> core::result::Result<WString, D::Error>
+            where
+                D: de::Deserializer<'de>,
+            {
+                de::Deserialize::deserialize(deserializer).map(WString)
+            }
+        }
+
+        deserializer.deserialize_newtype_struct(WSTRING_NAME, Visitor)
+    }
+}
+
+/// Encodes a character as a single UTF-16 code unit.
+pub(crate) fn encode_wchar(c: char) -> Result<u16> {
+    let mut units = [0; 2];
+    match c.encode_utf16(&mut units) {
+        [unit] => Ok(*unit),
+        _ => Err(Error::InvalidChar(c)),
+    }
+}
+
+/// Decodes a character from UTF-16 text holding exactly one.
+pub(crate) fn decode_wchar(s: &str) -> Result<char> {
+    let mut chars = s.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Ok(c),
+        _ => Err(Error::InvalidCharEncoding),
+    }
+}
+
+/// Encodes a string as UTF-16 code units in the byte order `E`.
+pub(crate) fn encode_utf16<E>(s: &str) -> Vec<u8>
+where
+    E: ByteOrder,
+{
+    let units: Vec<u16> = s.encode_utf16().collect();
+    let mut bytes = vec![0; units.len() * 2];
+    E::write_u16_into(&units, &mut bytes);
+    bytes
+}
+
+/// Decodes UTF-16 code units in the byte order `E`.
+pub(crate) fn decode_utf16<E>(bytes: &[u8]) -> Result<String>
+where
+    E: ByteOrder,
+{
+    if bytes.len() % 2 != 0 {
+        return Err(Error::InvalidUtf16Encoding);
+    }
+
+    let mut units = vec![0; bytes.len() / 2];
+    E::read_u16_into(bytes, &mut units);
+    String::from_utf16(&units).map_err(|_| Error::InvalidUtf16Encoding)
+}
+
+/// Decodes UTF-16 text in the byte order given by its byte order mark, or in
+/// big-endian byte order without one.
+pub(crate) fn decode_utf16_with_bom(bytes: &[u8]) -> Result<String> {
+    match bytes {
+        [a, b, rest @ ..] if [*a, *b] == BOM_LE => decode_utf16::<LittleEndian>(rest),
+        [a, b, rest @ ..] if [*a, *b] == BOM_BE => decode_utf16::<BigEndian>(rest),
+        _ => decode_utf16::<BigEndian>(bytes),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_utf16_with_bom() {
+        assert_eq!(decode_utf16_with_bom(&[0x00, 0x61]).unwrap(), "a");
+        assert_eq!(
+            decode_utf16_with_bom(&[0xfe, 0xff, 0x00, 0x61]).unwrap(),
+            "a"
+        );
+        assert_eq!(
+            decode_utf16_with_bom(&[0xff, 0xfe, 0x61, 0x00]).unwrap(),
+            "a"
+        );
+        assert!(decode_utf16_with_bom(&[0x00, 0x61, 0x00]).is_err());
+        assert!(decode_utf16_with_bom(&[0xd8, 0x00]).is_err());
+    }
+
+    #[test]
+    fn test_encode_wchar() {
+        assert_eq!(encode_wchar('a').unwrap(), 0x61);
+        assert_eq!(encode_wchar('ß').unwrap(), 0xdf);
+        assert!(encode_wchar('🦀').is_err());
+    }
+}
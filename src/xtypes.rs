@@ -0,0 +1,200 @@
+//! Wrappers giving the XTypes type information that serde does not convey.
+//!
+//! Serde tells a serializer how a value is built, but not the type it stands
+//! for, so it cannot tell whether the elements of a sequence are primitive or
+//! which extensibility kind a struct has. These wrappers state it where XCDR
+//! needs it. Other formats see a newtype around the value.
+
+use core::{
+    fmt,
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+};
+
+use serde::{de, ser};
+
+use crate::encapsulation::Extensibility;
+
+pub(crate) const DELIMITED_NAME: &str = "$cdr::Delimited";
+const FINAL_NAME: &str = "$cdr::Final";
+const APPENDABLE_NAME: &str = "$cdr::Appendable";
+const MUTABLE_NAME: &str = "$cdr::Mutable";
+pub(crate) const KEY_NAME: &str = "$cdr::Key";
+
+/// Returns the extensibility kind that a newtype with `name` gives the struct
+/// it wraps, if any.
+pub(crate) fn extensibility_of(name: &str) -> Option<Extensibility> {
+    match name {
+        FINAL_NAME => Some(Extensibility::Final),
+        APPENDABLE_NAME => Some(Extensibility::Appendable),
+        MUTABLE_NAME => Some(Extensibility::Mutable),
+        _ => None,
+    }
+}
+
+macro_rules! impl_wrapper {
+    ($ty:ident = $name:expr, $expecting:expr) => {
+        impl<T> From<T> for $ty<T> {
+            fn from(value: T) -> Self {
+                $ty(value)
+            }
+        }
+
+        impl<T> Deref for $ty<T> {
+            type Target = T;
+
+            fn deref(&self) -> &T {
+                &self.0
+            }
+        }
+
+        impl<T> DerefMut for $ty<T> {
+            fn deref_mut(&mut self) -> &mut T {
+                &mut self.0
+            }
+        }
+
+        impl<T> ser::Serialize for $ty<T>
+        where
+            T: ser::Serialize,
+        {
+            fn serialize<S>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error>
+            where
+                S: ser::Serializer,
+            {
+                serializer.serialize_newtype_struct($name, &self.0)
+            }
+        }
+
+        impl<'de, T> de::Deserialize<'de> for $ty<T>
+        where
+            T: de::Deserialize<'de>,
+        {
+            fn deserialize<D>(deserializer: D) -> core::result::Result<Self, D::Error>
+            where
+                D: de::Deserializer<'de>,
+            {
+                struct Visitor<T>(PhantomData<T>);
+
+                impl<'de, T> de::Visitor<'de> for Visitor<T>
+                where
+                    T: de::Deserialize<'de>,
+                {
+                    type Value = $ty<T>;
+
+                    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                        formatter.write_str($expecting)
+                    }
+
+                    fn visit_newtype_struct<D>(
+                        self,
+                        deserializer: D,
+                    ) -> core::result::Result<$ty<T>, D::Error>
+                    where
+                        D: de::Deserializer<'de>,
+                    {
+                        de::Deserialize::deserialize(deserializer).map($ty)
+                    }
+                }
+
+                deserializer.deserialize_newtype_struct($name, Visitor(PhantomData))
+            }
+        }
+    };
+}
+
+/// A sequence, an array or a map whose elements are not primitive, which
+/// XCDR2 precedes with a DHEADER holding its serialized length.
+///
+/// XTypes counts booleans, integers, floating point numbers and characters as
+/// primitive. Collections of anything else, such as strings, structs, enums
+/// standing for unions or other collections, need the DHEADER to be read by
+/// other implementations and are to be wrapped in `Delimited`. It is written
+/// even if the collection is empty, and has no effect in XCDR1.
+///
+/// ```rust
+/// use cdr::{Cdr2Le, Delimited, Infinite};
+///
+/// let names = Delimited(vec!["a".to_string()]);
+/// let encoded = cdr::serialize::<_, _, Cdr2Le>(&names, Infinite).unwrap();
+/// assert_eq!(&encoded[4..8], &[10, 0, 0, 0]);
+/// ```
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Delimited<T>(pub T);
+
+impl_wrapper!(Delimited = DELIMITED_NAME, "a delimited collection");
+
+/// A struct that is final wherever it appears, like one annotated with
+/// `@final` in IDL.
+///
+/// The members of a final struct are written back to back. Only structs can
+/// be wrapped.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Final<T>(pub T);
+
+impl_wrapper!(Final = FINAL_NAME, "a final struct");
+
+/// A struct that is appendable wherever it appears, like one annotated with
+/// `@appendable` in IDL.
+///
+/// In XCDR2 the struct is preceded by a DHEADER. Only structs can be wrapped.
+///
+/// ```rust
+/// use cdr::{Appendable, Cdr2Le, Infinite};
+/// use serde_derive::{Deserialize, Serialize};
+///
+/// #[derive(Deserialize, Serialize, PartialEq, Debug)]
+/// struct Inner {
+///     a: u16,
+/// }
+///
+/// #[derive(Deserialize, Serialize, PartialEq, Debug)]
+/// struct Outer {
+///     inner: Appendable<Inner>,
+/// }
+///
+/// let outer = Outer {
+///     inner: Appendable(Inner { a: 7 }),
+/// };
+/// let encoded = cdr::serialize::<_, _, Cdr2Le>(&outer, Infinite).unwrap();
+/// assert_eq!(&encoded[4..10], &[2, 0, 0, 0, 7, 0]);
+/// assert_eq!(cdr::deserialize::<Outer>(&encoded).unwrap(), outer);
+/// ```
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Appendable<T>(pub T);
+
+impl_wrapper!(Appendable = APPENDABLE_NAME, "an appendable struct");
+
+/// A struct that is mutable wherever it appears, like one annotated with
+/// `@mutable` in IDL.
+///
+/// Each member is written with a header holding its member id. Only structs
+/// can be wrapped.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Mutable<T>(pub T);
+
+impl_wrapper!(Mutable = MUTABLE_NAME, "a mutable struct");
+
+/// A key member of a struct, like one annotated with `@key` in IDL.
+///
+/// As a member of a mutable struct it is written with the must-understand
+/// flag of its header set. It has no effect anywhere else.
+///
+/// ```rust
+/// use cdr::{Infinite, Key, PlCdrLe};
+/// use serde_derive::{Deserialize, Serialize};
+///
+/// #[derive(Deserialize, Serialize, PartialEq, Debug)]
+/// struct Sample {
+///     id: Key<u16>,
+/// }
+///
+/// let sample = Sample { id: Key(7) };
+/// let encoded = cdr::serialize::<_, _, PlCdrLe>(&sample, Infinite).unwrap();
+/// assert_eq!(&encoded[4..8], &[0x00, 0x40, 4, 0]);
+/// assert_eq!(cdr::deserialize::<Sample>(&encoded).unwrap(), sample);
+/// ```
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Key<T>(pub T);
+
+impl_wrapper!(Key = KEY_NAME, "a key member");
@@ -1,17 +1,20 @@
 #![deny(warnings, clippy::all)]
 
-use std::{fmt::Debug, io::Cursor};
+use std::fmt::Debug;
 
 use cdr::{
-    BigEndian, Bounded, CdrBe, CdrLe, Error, Infinite, LittleEndian, PlCdrBe, PlCdrLe, Result,
+    Appendable, BigEndian, Bounded, Cdr2Be, Cdr2Le, CdrBe, CdrLe, CdrVersion, DCdr2Be, DCdr2Le,
+    Delimited, Encapsulated, EncapsulationHeader, EncapsulationKind, Error, Final, Infinite, Key,
+    LittleEndian, Mutable, PaddingMode, PathSegment, PlCdr2Be, PlCdr2Le, PlCdrBe, PlCdrLe,
+    PrimitiveArray, Primitives, Result, WChar, WString, WideEncoding,
 };
 use serde_derive::{Deserialize, Serialize};
 
 const ENCAPSULATION_HEADER_SIZE: u64 = 4;
 
-fn check<'de, T>(element: T, maybe_size: Option<u64>)
+fn check<T>(element: T, maybe_size: Option<u64>)
 where
-    T: serde::Serialize + serde::Deserialize<'de> + PartialEq + Debug,
+    T: serde::Serialize + serde::de::DeserializeOwned + PartialEq + Debug,
 {
     check_serialized_size(&element, maybe_size);
     check_round_trip(&element, maybe_size);
@@ -19,9 +22,9 @@ where
     check_size_limit(&element, maybe_size);
 }
 
-fn check_serialized_size<'de, T>(element: &T, maybe_size: Option<u64>)
+fn check_serialized_size<T>(element: &T, maybe_size: Option<u64>)
 where
-    T: serde::Serialize + serde::Deserialize<'de> + PartialEq + Debug,
+    T: serde::Serialize + serde::de::DeserializeOwned + PartialEq + Debug,
 {
     if let Some(serialized_size) = maybe_size {
         {
@@ -35,9 +38,9 @@ where
     }
 }
 
-fn check_round_trip<'de, T>(element: &T, maybe_size: Option<u64>)
+fn check_round_trip<T>(element: &T, maybe_size: Option<u64>)
 where
-    T: serde::Serialize + serde::Deserialize<'de> + PartialEq + Debug,
+    T: serde::Serialize + serde::de::DeserializeOwned + PartialEq + Debug,
 {
     let size = match maybe_size {
         Some(v) => v,
@@ -76,90 +79,88 @@ where
         let decoded = cdr::deserialize(&encoded).unwrap();
 
         assert_eq!(*element, decoded);
-        assert_eq!(size + ENCAPSULATION_HEADER_SIZE, encoded.len() as u64);
     }
     {
         let encoded = cdr::serialize::<_, _, PlCdrLe>(element, Infinite).unwrap();
         let decoded = cdr::deserialize(&encoded).unwrap();
 
         assert_eq!(*element, decoded);
-        assert_eq!(size + ENCAPSULATION_HEADER_SIZE, encoded.len() as u64);
     }
 }
 
-fn check_capacity_shortage<'de, T>(element: &T, maybe_size: Option<u64>)
+fn check_capacity_shortage<T>(element: &T, maybe_size: Option<u64>)
 where
-    T: serde::Serialize + serde::Deserialize<'de> + PartialEq + Debug,
+    T: serde::Serialize + serde::de::DeserializeOwned + PartialEq + Debug,
 {
     let mut buf = [0u8; 2000];
     if let Some(bound) = calc_invalid_size(element, maybe_size) {
         {
-            let mut buf = Cursor::new(&mut buf[0..bound as usize]);
+            let mut buf = &mut buf[0..bound as usize];
             assert!(cdr::ser::serialize_data_into::<_, _, _, BigEndian>(
                 &mut buf, &element, Infinite
             )
             .is_err());
         }
         {
-            let mut buf = Cursor::new(&mut buf[0..bound as usize]);
+            let mut buf = &mut buf[0..bound as usize];
             assert!(cdr::ser::serialize_data_into::<_, _, _, LittleEndian>(
                 &mut buf, &element, Infinite
             )
             .is_err());
         }
         {
-            let mut buf = Cursor::new(&mut buf[0..bound as usize]);
+            let mut buf = &mut buf[0..bound as usize];
             assert!(cdr::serialize_into::<_, _, _, CdrBe>(&mut buf, &element, Infinite).is_err());
         }
         {
-            let mut buf = Cursor::new(&mut buf[0..bound as usize]);
+            let mut buf = &mut buf[0..bound as usize];
             assert!(cdr::serialize_into::<_, _, _, CdrLe>(&mut buf, &element, Infinite).is_err());
         }
         {
-            let mut buf = Cursor::new(&mut buf[0..bound as usize]);
+            let mut buf = &mut buf[0..bound as usize];
             assert!(cdr::serialize_into::<_, _, _, PlCdrBe>(&mut buf, &element, Infinite).is_err());
         }
         {
-            let mut buf = Cursor::new(&mut buf[0..bound as usize]);
+            let mut buf = &mut buf[0..bound as usize];
             assert!(cdr::serialize_into::<_, _, _, PlCdrLe>(&mut buf, &element, Infinite).is_err());
         }
     } else {
         {
-            let mut buf = Cursor::new(&mut buf[0..0]);
+            let mut buf = &mut buf[0..0];
             assert!(cdr::ser::serialize_data_into::<_, _, _, BigEndian>(
                 &mut buf, &element, Infinite
             )
             .is_ok());
         }
         {
-            let mut buf = Cursor::new(&mut buf[0..0]);
+            let mut buf = &mut buf[0..0];
             assert!(cdr::ser::serialize_data_into::<_, _, _, LittleEndian>(
                 &mut buf, &element, Infinite
             )
             .is_ok());
         }
         {
-            let mut buf = Cursor::new(&mut buf[0..ENCAPSULATION_HEADER_SIZE as usize]);
+            let mut buf = &mut buf[0..ENCAPSULATION_HEADER_SIZE as usize];
             assert!(cdr::serialize_into::<_, _, _, CdrBe>(&mut buf, &element, Infinite).is_ok());
         }
         {
-            let mut buf = Cursor::new(&mut buf[0..ENCAPSULATION_HEADER_SIZE as usize]);
+            let mut buf = &mut buf[0..ENCAPSULATION_HEADER_SIZE as usize];
             assert!(cdr::serialize_into::<_, _, _, CdrLe>(&mut buf, &element, Infinite).is_ok());
         }
         {
-            let mut buf = Cursor::new(&mut buf[0..ENCAPSULATION_HEADER_SIZE as usize]);
+            let mut buf = &mut buf[0..ENCAPSULATION_HEADER_SIZE as usize];
             assert!(cdr::serialize_into::<_, _, _, PlCdrBe>(&mut buf, &element, Infinite).is_ok());
         }
         {
-            let mut buf = Cursor::new(&mut buf[0..ENCAPSULATION_HEADER_SIZE as usize]);
+            let mut buf = &mut buf[0..ENCAPSULATION_HEADER_SIZE as usize];
             assert!(cdr::serialize_into::<_, _, _, PlCdrLe>(&mut buf, &element, Infinite).is_ok());
         }
     }
 }
 
-fn check_size_limit<'de, T>(element: &T, maybe_size: Option<u64>)
+fn check_size_limit<T>(element: &T, maybe_size: Option<u64>)
 where
-    T: serde::Serialize + serde::Deserialize<'de> + PartialEq + Debug,
+    T: serde::Serialize + serde::de::DeserializeOwned + PartialEq + Debug,
 {
     if let Some(bound) = calc_invalid_size(element, maybe_size) {
         assert!(cdr::ser::serialize_data::<_, _, BigEndian>(&element, Bounded(bound)).is_err());
@@ -275,9 +276,54 @@ where
     }
 }
 
-fn calc_invalid_size<'de, T>(element: &T, maybe_size: Option<u64>) -> Option<u64>
+fn check_encapsulation<T, B, L>(element: T, size: u64)
+where
+    T: serde::Serialize + serde::de::DeserializeOwned + PartialEq + Debug,
+    B: cdr::Encapsulation,
+    L: cdr::Encapsulation,
+{
+    // XCDR2 data is padded to a multiple of four bytes.
+    let size = match B::VERSION {
+        CdrVersion::Xcdr1 => size,
+        CdrVersion::Xcdr2 => (size + 3) / 4 * 4,
+    };
+    {
+        let encoded = cdr::serialize::<_, _, B>(&element, Infinite).unwrap();
+        let decoded = cdr::deserialize(&encoded).unwrap();
+
+        assert_eq!(element, decoded);
+        assert_eq!(size + ENCAPSULATION_HEADER_SIZE, encoded.len() as u64);
+    }
+    {
+        let encoded = cdr::serialize::<_, _, L>(&element, Infinite).unwrap();
+        let decoded = cdr::deserialize(&encoded).unwrap();
+
+        assert_eq!(element, decoded);
+        assert_eq!(size + ENCAPSULATION_HEADER_SIZE, encoded.len() as u64);
+    }
+    for id in [B::ID, L::ID] {
+        let kind = EncapsulationKind::from_id(id).unwrap();
+        let total = size + ENCAPSULATION_HEADER_SIZE;
+        assert_eq!(
+            cdr::calc_serialized_size_with(&element, kind).unwrap(),
+            total
+        );
+        assert_eq!(
+            cdr::calc_serialized_size_bounded_with(&element, kind, total).unwrap(),
+            total
+        );
+    }
+    {
+        assert!(cdr::serialize::<_, _, B>(&element, Bounded(size)).is_ok());
+        assert!(cdr::serialize::<_, _, L>(&element, Bounded(size)).is_ok());
+        assert!(cdr::serialize::<_, _, B>(&element, Bounded(size - 1)).is_err());
+        assert!(cdr::serialize::<_, _, L>(&element, Bounded(size - 1)).is_err());
+    }
+}
+
+fn calc_invalid_size<T>(element: &T, maybe_size: Option<u64>) -> Option<u64>
 where
-    T: serde::Serialize + serde::Deserialize<'de> + PartialEq + Debug,
+    T: serde::Serialize + serde::de::DeserializeOwned + PartialEq + Debug,
 {
     match maybe_size {
         Some(v) if v > 0 => Some(v - 1),
@@ -295,8 +341,8 @@ where
 
 #[test]
 fn test_octet() {
-    check(std::u8::MIN, Some(1));
-    check(std::u8::MAX, Some(1));
+    check(u8::MIN, Some(1));
+    check(u8::MAX, Some(1));
 }
 
 #[test]
@@ -307,50 +353,50 @@ fn test_char() {
 
 #[test]
 fn test_unsigned_short() {
-    check(std::u16::MIN, Some(2));
-    check(std::u16::MAX, Some(2));
+    check(u16::MIN, Some(2));
+    check(u16::MAX, Some(2));
 }
 
 #[test]
 fn test_short() {
-    check(std::i16::MIN, Some(2));
-    check(std::i16::MAX, Some(2));
+    check(i16::MIN, Some(2));
+    check(i16::MAX, Some(2));
 }
 
 #[test]
 fn test_unsigned_long() {
-    check(std::u32::MIN, Some(4));
-    check(std::u32::MAX, Some(4));
+    check(u32::MIN, Some(4));
+    check(u32::MAX, Some(4));
 }
 
 #[test]
 fn test_long() {
-    check(std::i32::MIN, Some(4));
-    check(std::i32::MAX, Some(4));
+    check(i32::MIN, Some(4));
+    check(i32::MAX, Some(4));
 }
 
 #[test]
 fn test_unsigned_long_long() {
-    check(std::u64::MIN, Some(8));
-    check(std::u64::MAX, Some(8));
+    check(u64::MIN, Some(8));
+    check(u64::MAX, Some(8));
 }
 
 #[test]
 fn test_long_long() {
-    check(std::i64::MIN, Some(8));
-    check(std::i64::MAX, Some(8));
+    check(i64::MIN, Some(8));
+    check(i64::MAX, Some(8));
 }
 
 #[test]
 fn test_float() {
-    check(std::f32::MIN, Some(4));
-    check(std::f32::MAX, Some(4));
+    check(f32::MIN, Some(4));
+    check(f32::MAX, Some(4));
 }
 
 #[test]
 fn test_double() {
-    check(std::f64::MIN, Some(8));
-    check(std::f64::MAX, Some(8));
+    check(f64::MIN, Some(8));
+    check(f64::MAX, Some(8));
 }
 
 #[test]
@@ -363,6 +409,1122 @@ fn test_bool() {
 fn test_string() {
     check("".to_string(), Some(5));
     check("a".to_string(), Some(6));
+    check("Zürich".to_string(), Some(12));
+    check("🦀".to_string(), Some(9));
+}
+
+#[test]
+fn test_wchar() {
+    check(WChar('a'), Some(2));
+    check(WChar('ß'), Some(2));
+    check((1u8, WChar('x')), Some(1 + 1 + 2));
+    check_encapsulation::<_, Cdr2Be, Cdr2Le>((WChar('€'), WChar('a')), 4);
+    assert!(matches!(
+        cdr::serialize::<_, _, CdrBe>(&WChar('🦀'), Infinite),
+        Err(Error::InvalidChar('🦀'))
+    ));
+}
+
+#[test]
+fn test_wstring() {
+    check(WString::from(""), Some(4 + 2));
+    check(WString::from("héllo"), Some(4 + 12));
+    check(WString::from("🦀"), Some(4 + 6));
+    check_encapsulation::<_, Cdr2Be, Cdr2Le>(WString::from(""), 4);
+    check_encapsulation::<_, Cdr2Be, Cdr2Le>(WString::from("h🦀"), 4 + 6);
+
+    let encoded = cdr::serialize::<_, _, CdrLe>(&WString::from("hé"), Infinite).unwrap();
+    assert_eq!(
+        encoded,
+        vec![
+            0x00, 0x01, 0x00, 0x00, //
+            0x03, 0x00, 0x00, 0x00, // length in code units
+            0x68, 0x00, 0xe9, 0x00, //
+            0x00, 0x00,
+        ]
+    );
+
+    let encoded = cdr::serialize::<_, _, Cdr2Be>(&WString::from("hé"), Infinite).unwrap();
+    assert_eq!(
+        encoded,
+        vec![
+            0x00, 0x06, 0x00, 0x00, //
+            0x00, 0x00, 0x00, 0x04, // length in bytes
+            0x00, 0x68, 0x00, 0xe9,
+        ]
+    );
+}
+
+#[test]
+fn test_giop_wide_encoding() {
+    let mut encoded = Vec::new();
+    let mut serializer = cdr::ser::Serializer::<_, LittleEndian>::new(&mut encoded)
+        .with_wide_encoding(WideEncoding::Giop);
+    serde::Serialize::serialize(&(WChar('a'), WString::from("hé")), &mut serializer).unwrap();
+    assert_eq!(
+        encoded,
+        vec![
+            0x02, 0x00, 0x61, 0x00, // wchar prefixed with its length
+            0x04, 0x00, 0x00, 0x00, //
+            0x00, 0x68, 0x00, 0xe9,
+        ]
+    );
+
+    let encoded = vec![
+        0x04, 0xff, 0xfe, 0x61, // wchar with a byte order mark
+        0x00, 0x00, 0x00, 0x00, //
+        0x06, 0x00, 0x00, 0x00, //
+        0xff, 0xfe, 0x68, 0x00, // wstring with a byte order mark
+        0xe9, 0x00,
+    ];
+    let mut deserializer = cdr::Deserializer::<_, _, LittleEndian>::new(&encoded[..], Infinite)
+        .with_wide_encoding(WideEncoding::Giop);
+    assert_eq!(
+        (WChar('a'), WString::from("hé")),
+        serde::Deserialize::deserialize(&mut deserializer).unwrap()
+    );
+}
+
+#[test]
+fn test_borrowed() {
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Frame<'a> {
+        id: u32,
+        name: &'a str,
+        data: &'a [u8],
+    }
+
+    let frame = Frame {
+        id: 7,
+        name: "camera",
+        data: &[1, 2, 3],
+    };
+
+    let encoded = cdr::serialize::<_, _, CdrLe>(&frame, Infinite).unwrap();
+    let decoded = cdr::deserialize::<Frame>(&encoded).unwrap();
+    assert_eq!(frame, decoded);
+    assert!(encoded.as_ptr_range().contains(&decoded.name.as_ptr()));
+    assert!(encoded.as_ptr_range().contains(&decoded.data.as_ptr()));
+    assert!(cdr::deserialize_from::<_, Frame, _>(&encoded[..], Infinite).is_err());
+
+    let encoded = cdr::ser::serialize_data::<_, _, BigEndian>(&frame, Infinite).unwrap();
+    let decoded = cdr::de::deserialize_data::<Frame, BigEndian>(&encoded).unwrap();
+    assert_eq!(frame, decoded);
+    assert!(encoded.as_ptr_range().contains(&decoded.name.as_ptr()));
+}
+
+#[test]
+fn test_huge_length_prefix() {
+    // Lengths and counts of almost 4 GiB followed by a few bytes.
+    let bytes = [0xff, 0xff, 0xff, 0xf0, 1, 2, 3, 4];
+    assert!(cdr::de::deserialize_data::<Vec<u8>, BigEndian>(&bytes).is_err());
+    assert!(cdr::de::deserialize_data::<String, BigEndian>(&bytes).is_err());
+    assert!(cdr::de::deserialize_data::<WString, BigEndian>(&bytes).is_err());
+    assert!(
+        cdr::de::deserialize_data_from::<_, String, _, BigEndian>(&bytes[..], Infinite).is_err()
+    );
+    assert!(
+        cdr::de::deserialize_data_from::<_, Vec<u32>, _, BigEndian>(&bytes[..], Infinite).is_err()
+    );
+    assert!(matches!(
+        cdr::de::deserialize_data_from::<_, String, _, BigEndian>(&bytes[..], Bounded(16))
+            .unwrap_err(),
+        Error::SizeLimit
+    ));
+
+    struct SizeHint(Option<usize>);
+
+    impl<'de> serde::Deserialize<'de> for SizeHint {
+        fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+        where
+            D: serde::Deserializer<'de>,
+        {
+            struct Visitor;
+
+            impl<'de> serde::de::Visitor<'de> for Visitor {
+                type Value = SizeHint;
+
+                fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                    formatter.write_str("a sequence")
+                }
+
+                fn visit_seq<A>(self, seq: A) -> std::result::Result<SizeHint, A::Error>
+                where
+                    A: serde::de::SeqAccess<'de>,
+                {
+                    Ok(SizeHint(seq.size_hint()))
+                }
+            }
+
+            deserializer.deserialize_seq(Visitor)
+        }
+    }
+
+    let hint = cdr::de::deserialize_data::<SizeHint, BigEndian>(&bytes).unwrap();
+    assert_eq!(hint.0, Some(4));
+    let mut deserializer =
+        cdr::Deserializer::<_, _, BigEndian>::new(&bytes[..], Infinite).with_max_preallocation(2);
+    let hint: SizeHint = serde::Deserialize::deserialize(&mut deserializer).unwrap();
+    assert_eq!(hint.0, Some(2));
+
+    // Data longer than the maximum preallocation is still read.
+    let data = "x".repeat(1000);
+    let encoded = cdr::ser::serialize_data::<_, _, BigEndian>(&data, Infinite).unwrap();
+    let mut deserializer = cdr::Deserializer::<_, _, BigEndian>::new(&encoded[..], Infinite)
+        .with_max_preallocation(16);
+    let decoded: String = serde::Deserialize::deserialize(&mut deserializer).unwrap();
+    assert_eq!(data, decoded);
+}
+
+#[test]
+fn test_serialize_with() {
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Sample {
+        id: u16,
+        value: f64,
+        name: String,
+    }
+
+    fn check<C>(kind: EncapsulationKind)
+    where
+        C: cdr::Encapsulation,
+    {
+        let sample = Sample {
+            id: 3,
+            value: 0.5,
+            name: "a".to_string(),
+        };
+        let encoded = cdr::serialize_with(&sample, kind, Infinite).unwrap();
+        assert_eq!(
+            encoded,
+            cdr::serialize::<_, _, C>(&sample, Infinite).unwrap()
+        );
+        assert_eq!(sample, cdr::deserialize::<Sample>(&encoded).unwrap());
+
+        let mut written = Vec::new();
+        cdr::serialize_into_with(&mut written, &sample, kind, Infinite).unwrap();
+        assert_eq!(encoded, written);
+
+        let mut buf = vec![0xff; 3];
+        cdr::serialize_into_vec_with(&mut buf, &sample, kind, Infinite).unwrap();
+        assert_eq!(encoded, buf);
+
+        let mut buf = [0xff; 64];
+        let len = cdr::serialize_into_slice_with(&mut buf, &sample, kind, Infinite).unwrap();
+        assert_eq!(encoded, buf[..len]);
+
+        // Without the header, nor the padding that XCDR2 ends the data with.
+        let data = cdr::ser::serialize_data_with(&sample, kind, Infinite).unwrap();
+        assert_eq!(data, encoded[4..4 + data.len()]);
+        assert!(encoded.len() - 4 - data.len() < 4);
+        assert_eq!(
+            sample,
+            cdr::de::deserialize_data_with::<Sample>(&data, kind).unwrap()
+        );
+
+        let size = encoded.len() as u64 - ENCAPSULATION_HEADER_SIZE;
+        assert!(cdr::serialize_with(&sample, kind, Bounded(size)).is_ok());
+        assert!(cdr::serialize_with(&sample, kind, Bounded(size - 1)).is_err());
+    }
+
+    check::<CdrBe>(EncapsulationKind::CdrBe);
+    check::<CdrLe>(EncapsulationKind::CdrLe);
+    check::<PlCdrBe>(EncapsulationKind::PlCdrBe);
+    check::<PlCdrLe>(EncapsulationKind::PlCdrLe);
+    check::<Cdr2Be>(EncapsulationKind::Cdr2Be);
+    check::<Cdr2Le>(EncapsulationKind::Cdr2Le);
+    check::<DCdr2Be>(EncapsulationKind::DCdr2Be);
+    check::<DCdr2Le>(EncapsulationKind::DCdr2Le);
+    check::<PlCdr2Be>(EncapsulationKind::PlCdr2Be);
+    check::<PlCdr2Le>(EncapsulationKind::PlCdr2Le);
+
+    // A header given at runtime keeps its options, apart from the padding.
+    let header = EncapsulationHeader {
+        kind: EncapsulationKind::CdrBe,
+        options: [0x12, 0x35],
+    };
+    let encoded = cdr::serialize_with(&1_u8, header, Infinite).unwrap();
+    assert_eq!(encoded, vec![0x00, 0x00, 0x12, 0x34, 0x01]);
+    assert_eq!(
+        cdr::calc_serialized_size_with(&1_u8, header).unwrap(),
+        encoded.len() as u64
+    );
+
+    let header = EncapsulationHeader {
+        kind: EncapsulationKind::Cdr2Le,
+        options: [0x12, 0x34],
+    };
+    let encoded = cdr::serialize_with(&1_u8, header, Infinite).unwrap();
+    let (decoded, read) = cdr::deserialize_with_header::<u8>(&encoded).unwrap();
+    assert_eq!(decoded, 1);
+    assert_eq!(read.options, [0x12, 0x37]);
+    assert_eq!(cdr::serialize_with(&1_u8, read, Infinite).unwrap(), encoded);
+}
+
+#[test]
+fn test_serialize_into_slice() {
+    let value = (1_u8, 2_u32, "abc".to_string());
+
+    let mut buf = [0xff; 32];
+    let len =
+        cdr::ser::serialize_data_into_slice::<_, _, BigEndian>(&mut buf, &value, Infinite).unwrap();
+    let expected = cdr::ser::serialize_data::<_, _, BigEndian>(&value, Infinite).unwrap();
+    assert_eq!(&buf[..len], &expected[..]);
+
+    let mut buf = [0; 32];
+    let len = cdr::serialize_into_slice::<_, _, Cdr2Le>(&mut buf, &value, Infinite).unwrap();
+    let expected = cdr::serialize::<_, _, Cdr2Le>(&value, Infinite).unwrap();
+    assert_eq!(&buf[..len], &expected[..]);
+    assert_eq!(value, cdr::deserialize(&buf[..len]).unwrap());
+
+    let mut buf = [0; 8];
+    match cdr::serialize_into_slice::<_, _, Cdr2Le>(&mut buf, &value, Infinite) {
+        Err(Error::BufferTooSmall { needed }) => assert_eq!(needed, expected.len() as u64),
+        other => panic!("unexpected result: {:?}", other),
+    }
+    assert!(cdr::serialize_into_slice::<_, _, Cdr2Le>(&mut [0; 32], &value, Bounded(4)).is_err());
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_io_errors() {
+    use std::io::ErrorKind;
+
+    // Readers and writers of `std::io` report running out of input or output
+    // as any other error of theirs.
+    let encoded = cdr::serialize::<_, _, CdrBe>(&1_u32, Infinite).unwrap();
+    match cdr::deserialize_from::<_, u32, _>(&encoded[..6], Infinite) {
+        Err(Error::Io(e)) => assert_eq!(e.kind(), ErrorKind::UnexpectedEof),
+        other => panic!("unexpected result: {:?}", other),
+    }
+    let mut buf = [0; 6];
+    match cdr::serialize_into::<_, _, _, CdrBe>(&mut buf[..], &1_u32, Infinite) {
+        Err(Error::Io(e)) => assert_eq!(e.kind(), ErrorKind::WriteZero),
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn test_deserialize_with_header() {
+    let encoded = cdr::serialize::<_, _, PlCdrBe>(&(1_u8, 2_u32), Infinite).unwrap();
+    let (decoded, header) = cdr::deserialize_with_header::<(u8, u32)>(&encoded).unwrap();
+    assert_eq!(decoded, (1, 2));
+    assert_eq!(
+        header,
+        EncapsulationHeader {
+            kind: EncapsulationKind::PlCdrBe,
+            options: [0, 0],
+        }
+    );
+    assert_eq!(header.representation_id(), [0, 2]);
+    assert_eq!(header.endianness(), cdr::Endianness::Big);
+
+    let mut encoded = cdr::serialize::<_, _, CdrLe>(&7_u16, Infinite).unwrap();
+    encoded[2] = 0x5a;
+    let (decoded, header) =
+        cdr::deserialize_from_with_header::<_, u16, _>(&encoded[..], Infinite).unwrap();
+    assert_eq!(decoded, 7);
+    assert_eq!(header.kind, EncapsulationKind::CdrLe);
+    assert_eq!(header.options, [0x5a, 0]);
+    assert_eq!(header.endianness(), cdr::Endianness::Little);
+}
+
+#[test]
+fn test_options_padding() {
+    let encoded = cdr::serialize::<_, _, Cdr2Le>(&(1_u16, 2_u8), Infinite).unwrap();
+    assert_eq!(
+        encoded,
+        vec![0x00, 0x07, 0x00, 0x01, 0x01, 0x00, 0x02, 0x00]
+    );
+    let (decoded, header) = cdr::deserialize_with_header::<(u16, u8)>(&encoded).unwrap();
+    assert_eq!(decoded, (1, 2));
+    assert_eq!(header.padding(), 1);
+    assert_eq!(
+        cdr::deserialize_from::<_, (u16, u8), _>(&encoded[..], Infinite).unwrap(),
+        (1, 2)
+    );
+
+    // The padding replaces whatever the low bits of the options hold.
+    enum Cdr2LeWithOptions {}
+
+    impl cdr::Encapsulation for Cdr2LeWithOptions {
+        type E = LittleEndian;
+        const ID: [u8; 2] = Cdr2Le::ID;
+        const OPTION: [u8; 2] = [0x12, 0x03];
+        const VERSION: CdrVersion = CdrVersion::Xcdr2;
+    }
+
+    let encoded = cdr::serialize::<_, _, Cdr2LeWithOptions>(&(1_u16, 2_u8), Infinite).unwrap();
+    assert_eq!(&encoded[2..4], &[0x12, 0x01]);
+    let mut written = Vec::new();
+    cdr::serialize_into::<_, _, _, Cdr2LeWithOptions>(&mut written, &(1_u16, 2_u8), Infinite)
+        .unwrap();
+    assert_eq!(encoded, written);
+
+    // XCDR1 data is not padded, but the declared padding is read.
+    let encoded = cdr::serialize::<_, _, CdrLe>(&(1_u16, 2_u8), Infinite).unwrap();
+    assert_eq!(encoded, vec![0x00, 0x01, 0x00, 0x00, 0x01, 0x00, 0x02]);
+    assert_eq!(
+        cdr::deserialize::<(u16, u8)>(&[0x00, 0x01, 0x00, 0x01, 0x01, 0x00, 0x02, 0x00]).unwrap(),
+        (1, 2)
+    );
+
+    // The declared padding must be present and end on a multiple of four bytes.
+    assert!(cdr::deserialize::<(u16, u8)>(&[0x00, 0x07, 0x00, 0x01, 0x01, 0x00, 0x02]).is_err());
+    assert!(matches!(
+        cdr::deserialize::<(u16, u8)>(&[0x00, 0x07, 0x00, 0x02, 0x01, 0x00, 0x02, 0x00, 0x00]),
+        Err(Error::InvalidEncapsulation)
+    ));
+}
+
+#[test]
+fn test_trailing_bytes() {
+    let mut encoded = cdr::serialize::<_, _, CdrBe>(&(1_u16, 2_u8), Infinite).unwrap();
+    assert_eq!(
+        cdr::deserialize_strict::<(u16, u8)>(&encoded).unwrap(),
+        (1, 2)
+    );
+    encoded.extend_from_slice(&[0, 0]);
+    assert_eq!(cdr::deserialize::<(u16, u8)>(&encoded).unwrap(), (1, 2));
+    assert!(matches!(
+        cdr::deserialize_strict::<(u16, u8)>(&encoded),
+        Err(Error::TrailingBytes(2))
+    ));
+
+    // Padding ending XCDR2 data is not left over, whether declared or not.
+    let mut encoded = cdr::serialize::<_, _, Cdr2Be>(&(1_u16, 2_u8), Infinite).unwrap();
+    assert_eq!(
+        cdr::deserialize_strict::<(u16, u8)>(&encoded).unwrap(),
+        (1, 2)
+    );
+    encoded[3] = 0;
+    assert_eq!(
+        cdr::deserialize_strict::<(u16, u8)>(&encoded).unwrap(),
+        (1, 2)
+    );
+    encoded.push(0);
+    assert!(matches!(
+        cdr::deserialize_strict::<(u16, u8)>(&encoded),
+        Err(Error::TrailingBytes(2))
+    ));
+
+    let encoded = [0, 1, 2, 3, 4];
+    assert_eq!(
+        cdr::de::deserialize_data_strict::<u32, BigEndian>(&encoded[..4]).unwrap(),
+        0x0001_0203
+    );
+    assert!(matches!(
+        cdr::de::deserialize_data_strict::<u32, BigEndian>(&encoded),
+        Err(Error::TrailingBytes(1))
+    ));
+
+    let mut deserializer = cdr::Deserializer::<_, _, BigEndian>::new(&encoded[..], Infinite);
+    let _: u16 = serde::Deserialize::deserialize(&mut deserializer).unwrap();
+    assert!(matches!(deserializer.end(), Err(Error::TrailingBytes(3))));
+    assert!(deserializer.end().is_ok());
+}
+
+#[test]
+fn test_padding_mode() {
+    let encoded = [0x01, 0xaa, 0x00, 0x02];
+    let deserialize = |padding_mode| {
+        let mut deserializer = cdr::Deserializer::<_, _, BigEndian>::from_slice(&encoded, Infinite)
+            .with_padding_mode(padding_mode);
+        <(u8, u16) as serde::Deserialize>::deserialize(&mut deserializer)
+    };
+    assert_eq!(deserialize(PaddingMode::Lenient).unwrap(), (1_u8, 2_u16));
+    assert_eq!(deserialize(PaddingMode::Normal).unwrap(), (1_u8, 2_u16));
+    assert!(matches!(
+        deserialize(PaddingMode::Strict).unwrap_err(),
+        Error::InvalidPadding(1)
+    ));
+
+    // Padding declared in the encapsulation options but missing.
+    let encoded = [0x00, 0x07, 0x00, 0x03, 0x01, 0x02];
+    assert!(cdr::deserialize::<(u8, u8)>(&encoded).is_err());
+    assert_eq!(
+        cdr::deserialize_with_padding_mode::<(u8, u8)>(&encoded, PaddingMode::Lenient).unwrap(),
+        (1, 2)
+    );
+
+    let encoded = [0x00, 0x07, 0x00, 0x02, 0x01, 0x02, 0x00, 0x01];
+    assert!(cdr::deserialize::<(u8, u8)>(&encoded).is_ok());
+    assert!(matches!(
+        cdr::deserialize_with_padding_mode::<(u8, u8)>(&encoded, PaddingMode::Strict).unwrap_err(),
+        Error::InvalidPadding(7)
+    ));
+}
+
+#[test]
+fn test_error_position() {
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Point {
+        x: u16,
+        #[serde(rename = "visible@5")]
+        visible: bool,
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Path {
+        id: u32,
+        points: Vec<Point>,
+    }
+
+    let path = Path {
+        id: 1,
+        points: vec![
+            Point {
+                x: 2,
+                visible: true,
+            },
+            Point {
+                x: 3,
+                visible: false,
+            },
+        ],
+    };
+    let mut encoded = cdr::serialize::<_, _, CdrBe>(&path, Infinite).unwrap();
+    // The header, id, count, points[0] and x of points[1] come before.
+    encoded[4 + 4 + 4 + 4 + 2] = 7;
+
+    assert!(matches!(
+        cdr::deserialize::<Path>(&encoded),
+        Err(Error::InvalidBoolEncoding(7))
+    ));
+
+    let err = cdr::deserialize_with_error_positions::<Path>(&encoded).unwrap_err();
+    assert!(matches!(err.inner(), Error::InvalidBoolEncoding(7)));
+    assert_eq!(err.offset(), Some(18));
+    assert_eq!(
+        err.path(),
+        [
+            PathSegment::Field {
+                struct_name: "Path",
+                field: "points",
+            },
+            PathSegment::Index(1),
+            PathSegment::Field {
+                struct_name: "Point",
+                field: "visible",
+            },
+        ]
+    );
+    assert_eq!(
+        err.to_string(),
+        "expected 0 or 1, found 7 at offset 18 in Path.points[1].Point.visible"
+    );
+
+    let mut deserializer = cdr::Deserializer::<_, _, BigEndian>::from_slice(&[0, 0], Infinite)
+        .with_error_positions(true);
+    let err = <(u16, u32) as serde::Deserialize>::deserialize(&mut deserializer).unwrap_err();
+    assert_eq!(err.offset(), Some(2));
+    assert_eq!(err.path(), [PathSegment::Index(1)]);
+    assert!(matches!(err.inner(), Error::UnexpectedEof));
+}
+
+#[test]
+fn test_recursion_limit() {
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    enum List {
+        Cons(Box<List>),
+        Nil,
+    }
+
+    fn list(len: usize) -> List {
+        (0..len).fold(List::Nil, |tail, _| List::Cons(Box::new(tail)))
+    }
+
+    let encoded = cdr::ser::serialize_data::<_, _, BigEndian>(&list(100), Infinite).unwrap();
+    assert_eq!(
+        cdr::de::deserialize_data::<List, BigEndian>(&encoded).unwrap(),
+        list(100)
+    );
+
+    // A hostile input nesting a million levels deep.
+    let encoded = vec![0; 4_000_000];
+    assert!(matches!(
+        cdr::de::deserialize_data::<List, BigEndian>(&encoded).unwrap_err(),
+        Error::RecursionLimitExceeded
+    ));
+    let mut serializer = cdr::Serializer::<_, BigEndian>::new(Vec::new()).with_max_depth(128);
+    assert!(matches!(
+        serde::Serialize::serialize(&list(1000), &mut serializer),
+        Err(Error::RecursionLimitExceeded)
+    ));
+
+    // Writing is not limited by default, and reading allows deep enough
+    // nesting for lists of a hundred nodes, each counting as a struct and an
+    // option.
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Node {
+        value: u8,
+        next: Option<Box<Node>>,
+    }
+
+    fn chain(len: usize) -> Node {
+        let next = (1..len).fold(None, |next, _| Some(Box::new(Node { value: 1, next })));
+        Node { value: 1, next }
+    }
+
+    let encoded = cdr::serialize::<_, _, CdrBe>(&chain(99), Infinite).unwrap();
+    assert_eq!(cdr::calc_serialized_size(&chain(99)), encoded.len() as u64);
+    assert_eq!(cdr::deserialize::<Node>(&encoded).unwrap(), chain(99));
+
+    let encoded = cdr::serialize::<_, _, CdrBe>(&chain(200), Infinite).unwrap();
+    assert!(matches!(
+        cdr::deserialize::<Node>(&encoded),
+        Err(Error::RecursionLimitExceeded)
+    ));
+    assert_eq!(
+        cdr::deserialize_with_max_depth::<Node>(&encoded, 400).unwrap(),
+        chain(200)
+    );
+
+    let value = (1_u8, (2_u8,));
+    let mut encoded = Vec::new();
+    let mut serializer = cdr::Serializer::<_, BigEndian>::new(&mut encoded).with_max_depth(2);
+    serde::Serialize::serialize(&value, &mut serializer).unwrap();
+    let mut serializer = cdr::Serializer::<_, BigEndian>::new(Vec::new()).with_max_depth(1);
+    assert!(matches!(
+        serde::Serialize::serialize(&value, &mut serializer),
+        Err(Error::RecursionLimitExceeded)
+    ));
+
+    let mut deserializer =
+        cdr::Deserializer::<_, _, BigEndian>::from_slice(&encoded, Infinite).with_max_depth(2);
+    let decoded: (u8, (u8,)) = serde::Deserialize::deserialize(&mut deserializer).unwrap();
+    assert_eq!(decoded, value);
+    let mut deserializer =
+        cdr::Deserializer::<_, _, BigEndian>::from_slice(&encoded, Infinite).with_max_depth(1);
+    assert!(matches!(
+        <(u8, (u8,)) as serde::Deserialize>::deserialize(&mut deserializer).unwrap_err(),
+        Error::RecursionLimitExceeded
+    ));
+}
+
+#[test]
+fn test_primitives() {
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Bulk {
+        tag: u8,
+        values: Primitives<f64>,
+        position: PrimitiveArray<f32, 3>,
+        ids: Primitives<i16>,
+        empty: Primitives<u64>,
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Plain {
+        tag: u8,
+        values: Vec<f64>,
+        position: [f32; 3],
+        ids: Vec<i16>,
+        empty: Vec<u64>,
+    }
+
+    fn check<C>()
+    where
+        C: cdr::Encapsulation,
+    {
+        // Longer than the chunks the serializer gathers values in.
+        let values: Vec<f64> = (0..100).map(|i| f64::from(i) * -1.25).collect();
+        let bulk = Bulk {
+            tag: 1,
+            values: Primitives(values.clone()),
+            position: PrimitiveArray([1.0, 2.0, 3.0]),
+            ids: Primitives(vec![-1, 2, 3]),
+            empty: Primitives(vec![]),
+        };
+        let plain = Plain {
+            tag: 1,
+            values,
+            position: [1.0, 2.0, 3.0],
+            ids: vec![-1, 2, 3],
+            empty: vec![],
+        };
+
+        let encoded = cdr::serialize::<_, _, C>(&bulk, Infinite).unwrap();
+        assert_eq!(
+            encoded,
+            cdr::serialize::<_, _, C>(&plain, Infinite).unwrap()
+        );
+        let mut buf = vec![0; encoded.len()];
+        assert_eq!(
+            cdr::serialize_into_slice::<_, _, C>(&mut buf, &bulk, Infinite).unwrap(),
+            encoded.len()
+        );
+        assert_eq!(buf, encoded);
+        assert_eq!(bulk, cdr::deserialize_strict(&encoded).unwrap());
+        assert_eq!(bulk, cdr::deserialize_from(&encoded[..], Infinite).unwrap());
+    }
+
+    check::<CdrBe>();
+    check::<CdrLe>();
+    check::<PlCdrBe>();
+    check::<PlCdrLe>();
+    check::<Cdr2Be>();
+    check::<Cdr2Le>();
+    check::<DCdr2Be>();
+    check::<DCdr2Le>();
+    check::<PlCdr2Be>();
+    check::<PlCdr2Le>();
+
+    // Other formats see plain sequences and arrays.
+    let position = PrimitiveArray([1.0_f32, 2.0, 3.0]);
+    let encoded = bincode::serialize(&position).unwrap();
+    assert_eq!(encoded, bincode::serialize(&[1.0_f32, 2.0, 3.0]).unwrap());
+    assert_eq!(position, bincode::deserialize(&encoded).unwrap());
+    let ids = Primitives(vec![-1_i16, 2, 3]);
+    let encoded = bincode::serialize(&ids).unwrap();
+    assert_eq!(encoded, bincode::serialize(&vec![-1_i16, 2, 3]).unwrap());
+    assert_eq!(ids, bincode::deserialize(&encoded).unwrap());
+}
+
+#[test]
+fn test_incremental_size_limit() {
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Sample {
+        id: u32,
+        name: Option<String>,
+        points: Vec<Point>,
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Point {
+        x: f64,
+        tag: Option<u8>,
+    }
+
+    let sample = Sample {
+        id: 1,
+        name: None,
+        points: vec![
+            Point {
+                x: 0.5,
+                tag: Some(1),
+            },
+            Point { x: 1.5, tag: None },
+        ],
+    };
+
+    for kind in [
+        EncapsulationKind::CdrBe,
+        EncapsulationKind::PlCdrLe,
+        EncapsulationKind::Cdr2Be,
+        EncapsulationKind::DCdr2Le,
+        EncapsulationKind::PlCdr2Be,
+    ] {
+        let encoded = cdr::serialize_with(&sample, kind, Infinite).unwrap();
+        let size = encoded.len() as u64 - ENCAPSULATION_HEADER_SIZE;
+        assert_eq!(
+            cdr::serialize_with(&sample, kind, Bounded(size)).unwrap(),
+            encoded
+        );
+        assert!(matches!(
+            cdr::serialize_with(&sample, kind, Bounded(size - 1)),
+            Err(Error::SizeLimit)
+        ));
+
+        let mut written = Vec::new();
+        cdr::serialize_into_with(&mut written, &sample, kind, Bounded(size)).unwrap();
+        assert_eq!(written, encoded);
+
+        // Nothing is written when the limit is exceeded.
+        let mut written = Vec::new();
+        assert!(cdr::serialize_into_with(&mut written, &sample, kind, Bounded(size - 1)).is_err());
+        assert!(written.is_empty());
+    }
+
+    let encoded = cdr::serialize::<_, _, Cdr2Le>(&sample, Infinite).unwrap();
+    let mut buf = vec![0xff; encoded.len()];
+    assert_eq!(
+        cdr::serialize_into_slice::<_, _, Cdr2Le>(&mut buf, &sample, Infinite).unwrap(),
+        encoded.len()
+    );
+    assert_eq!(buf, encoded);
+    assert!(matches!(
+        cdr::serialize_into_slice::<_, _, Cdr2Le>(&mut buf[..10], &sample, Infinite),
+        Err(Error::BufferTooSmall { needed }) if needed == encoded.len() as u64
+    ));
+
+    let mut serializer =
+        cdr::Serializer::<_, BigEndian>::new(Vec::new()).with_size_limit(Bounded(6));
+    assert!(serde::Serialize::serialize(&(1_u32, 2_u16), &mut serializer).is_ok());
+    assert!(matches!(
+        serde::Serialize::serialize(&1_u8, &mut serializer),
+        Err(Error::SizeLimit)
+    ));
+}
+
+#[test]
+fn test_reuse_buffer() {
+    let mut buf = Vec::with_capacity(64);
+    let ptr = buf.as_ptr();
+    cdr::serialize_into_vec::<_, _, Cdr2Le>(&mut buf, &(1_u8, "abc"), Infinite).unwrap();
+    assert_eq!(
+        buf,
+        cdr::serialize::<_, _, Cdr2Le>(&(1_u8, "abc"), Infinite).unwrap()
+    );
+    cdr::serialize_into_vec::<_, _, CdrBe>(&mut buf, &7_u16, Infinite).unwrap();
+    assert_eq!(
+        buf,
+        cdr::serialize::<_, _, CdrBe>(&7_u16, Infinite).unwrap()
+    );
+    assert_eq!(buf.as_ptr(), ptr);
+
+    cdr::ser::serialize_data_into_vec::<_, _, BigEndian>(&mut buf, &7_u16, Infinite).unwrap();
+    assert_eq!(buf, [0, 7]);
+
+    let mut serializer = cdr::Serializer::<_, BigEndian>::new(Vec::new());
+    serde::Serialize::serialize(&1_u8, &mut serializer).unwrap();
+    assert_eq!(serializer.get_ref(), &[1]);
+    serializer.reset();
+    assert!(serializer.get_ref().is_empty());
+    serde::Serialize::serialize(&2_u32, &mut serializer).unwrap();
+    assert_eq!(serializer.into_inner(), [0, 0, 0, 2]);
+
+    let mut serializer = cdr::Serializer::<_, BigEndian>::new(Vec::new()).with_max_depth(1);
+    assert!(serde::Serialize::serialize(&((1_u8,),), &mut serializer).is_err());
+    serializer.reset();
+    serde::Serialize::serialize(&(1_u8,), &mut serializer).unwrap();
+    assert_eq!(serializer.into_inner(), [1]);
+}
+
+#[test]
+fn test_alignment_origin() {
+    let value = (1_u32, 2_u64, "ab", 3_u16);
+    let whole = cdr::ser::serialize_data::<_, _, BigEndian>(&((0_u8, 0_u8, 0_u8), value), Infinite)
+        .unwrap();
+
+    let mut serializer = cdr::Serializer::<_, BigEndian>::new_at(Vec::new(), 3);
+    serde::Serialize::serialize(&value, &mut serializer).unwrap();
+    let fragment = serializer.into_inner();
+    assert_eq!(fragment, whole[3..]);
+
+    let mut deserializer =
+        cdr::Deserializer::<_, _, BigEndian>::from_slice_at(&fragment, Infinite, 3);
+    let decoded: (u32, u64, String, u16) =
+        serde::Deserialize::deserialize(&mut deserializer).unwrap();
+    assert_eq!(decoded, (1, 2, "ab".to_string(), 3));
+
+    let mut padded = fragment.clone();
+    padded[0] = 1;
+    let mut deserializer =
+        cdr::Deserializer::<_, _, BigEndian>::from_slice_at(&padded, Infinite, 3)
+            .with_padding_mode(PaddingMode::Strict);
+    let err = <(u32, u64, String, u16) as serde::Deserialize>::deserialize(&mut deserializer)
+        .unwrap_err();
+    assert!(matches!(err, Error::InvalidPadding(0)));
+}
+
+#[test]
+fn test_encapsulated() {
+    check(Encapsulated(1_u8), Some(4 + 2));
+    check(
+        (1_u8, Encapsulated((2_u8, 3_u32)), 4_u16),
+        Some(4 + 4 + 8 + 2),
+    );
+    check(Encapsulated(Encapsulated(1_u64)), Some(4 + 4 + 4 + 8 + 8));
+    check_encapsulation::<_, Cdr2Be, Cdr2Le>(Encapsulated((2_u8, 3_u32)), 4 + 8);
+
+    let encoded = cdr::ser::serialize_data::<_, _, BigEndian>(
+        &(1_u8, Encapsulated((2_u8, 3_u32)), 4_u16),
+        Infinite,
+    )
+    .unwrap();
+    assert_eq!(
+        encoded,
+        vec![
+            0x01, 0x00, 0x00, 0x00, //
+            0x00, 0x00, 0x00, 0x08, // length
+            0x00, 0x02, 0x00, 0x00, // byte-order flag
+            0x00, 0x00, 0x00, 0x03, //
+            0x00, 0x04,
+        ]
+    );
+
+    // The encapsulation is read in its own byte order.
+    let encoded = [
+        0x01, 0x00, 0x00, 0x00, //
+        0x00, 0x00, 0x00, 0x0a, //
+        0x01, 0x02, 0x00, 0x00, //
+        0x03, 0x00, 0x00, 0x00, //
+        0xff, 0xff, 0x00, 0x04,
+    ];
+    let decoded =
+        cdr::de::deserialize_data::<(u8, Encapsulated<(u8, u32)>, u16), BigEndian>(&encoded)
+            .unwrap();
+    assert_eq!(decoded, (1, Encapsulated((2, 3)), 4));
+
+    let mut encoded = encoded;
+    encoded[11] = 0x09;
+    encoded[8] = 0x02;
+    assert!(matches!(
+        cdr::de::deserialize_data::<(u8, Encapsulated<(u8, u32)>), BigEndian>(&encoded)
+            .unwrap_err(),
+        Error::InvalidEncapsulation
+    ));
+    encoded[8] = 0x01;
+    encoded[7] = 0x06;
+    assert!(matches!(
+        cdr::de::deserialize_data::<(u8, Encapsulated<(u8, u32)>), BigEndian>(&encoded)
+            .unwrap_err(),
+        Error::LengthExceeded
+    ));
+
+    // Recursive types still compile with encapsulations nested at runtime.
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    struct Tree(Vec<Encapsulated<Tree>>);
+
+    check(
+        Tree(vec![Encapsulated(Tree(vec![])), Encapsulated(Tree(vec![]))]),
+        Some(4 + 2 * (4 + 4 + 4)),
+    );
+}
+
+#[test]
+fn test_giop_request() {
+    use cdr::giop::{self, Message, RequestHeader, TargetAddress, Version, RESPONSE_EXPECTED};
+
+    let request = Message::Request(RequestHeader {
+        request_id: 5,
+        response_flags: RESPONSE_EXPECTED,
+        target: TargetAddress::KeyAddr(b"ab".to_vec()),
+        operation: "op".to_string(),
+        service_context: Vec::new(),
+        requesting_principal: Vec::new(),
+    });
+
+    let encoded = giop::serialize_message::<_, BigEndian>(Version::V1_0, &request, &7_u32).unwrap();
+    assert_eq!(
+        encoded,
+        vec![
+            b'G', b'I', b'O', b'P', 0x01, 0x00, 0x00, 0x00, //
+            0x00, 0x00, 0x00, 0x24, // message size
+            0x00, 0x00, 0x00, 0x00, // service context
+            0x00, 0x00, 0x00, 0x05, // request id
+            0x01, 0x00, 0x00, 0x00, // response expected
+            0x00, 0x00, 0x00, 0x02, //
+            b'a', b'b', 0x00, 0x00, // object key
+            0x00, 0x00, 0x00, 0x03, //
+            b'o', b'p', 0x00, 0x00, // operation
+            0x00, 0x00, 0x00, 0x00, // requesting principal
+            0x00, 0x00, 0x00, 0x07, // body
+        ]
+    );
+    let (header, message, body) = giop::deserialize_message(&encoded).unwrap();
+    assert_eq!(header.message_size, 36);
+    assert_eq!(message, request);
+    assert_eq!(body.deserialize::<u32>().unwrap(), 7);
+
+    let encoded =
+        giop::serialize_message::<_, LittleEndian>(Version::V1_2, &request, &9_u64).unwrap();
+    assert_eq!(
+        encoded,
+        vec![
+            b'G', b'I', b'O', b'P', 0x01, 0x02, 0x01, 0x00, //
+            0x2c, 0x00, 0x00, 0x00, // message size
+            0x05, 0x00, 0x00, 0x00, // request id
+            0x03, 0x00, 0x00, 0x00, // response flags
+            0x00, 0x00, 0x00, 0x00, // target address
+            0x02, 0x00, 0x00, 0x00, //
+            b'a', b'b', 0x00, 0x00, //
+            0x03, 0x00, 0x00, 0x00, //
+            b'o', b'p', 0x00, 0x00, // operation
+            0x00, 0x00, 0x00, 0x00, // service context
+            0x00, 0x00, 0x00, 0x00, // padding of the body
+            0x09, 0x00, 0x00, 0x00, //
+            0x00, 0x00, 0x00, 0x00,
+        ]
+    );
+    let (header, message, body) = giop::deserialize_message(&encoded).unwrap();
+    assert_eq!(header.endianness, cdr::Endianness::Little);
+    assert_eq!(message, request);
+    assert_eq!(body.position(), 48);
+    assert_eq!(body.deserialize::<u64>().unwrap(), 9);
+
+    // Wide characters and strings are sized as GIOP writes them.
+    let args = (WChar('é'), WString::from("ab"), 1_u8);
+    let encoded = giop::serialize_message::<_, BigEndian>(Version::V1_2, &request, &args).unwrap();
+    let (_, _, body) = giop::deserialize_message(&encoded).unwrap();
+    assert_eq!(body.as_bytes().len(), 3 + 1 + 4 + 4 + 1);
+    assert_eq!(
+        giop::calc_body_size(&args, body.position()).unwrap(),
+        body.as_bytes().len() as u64
+    );
+    assert_eq!(giop::calc_body_size(&args, 1).unwrap(), 3 + 4 + 4 + 1);
+
+    // Without a body there is no padding.
+    let encoded = giop::serialize_message::<_, BigEndian>(Version::V1_2, &request, &()).unwrap();
+    assert_eq!(encoded.len(), 44);
+    let (_, _, body) = giop::deserialize_message(&encoded).unwrap();
+    assert!(body.as_bytes().is_empty());
+
+    assert!(matches!(
+        giop::deserialize_message(&encoded[..43]),
+        Err(Error::UnexpectedEof)
+    ));
+    let mut longer = encoded.clone();
+    longer.push(0);
+    assert!(matches!(
+        giop::deserialize_message(&longer),
+        Err(Error::TrailingBytes(1))
+    ));
+}
+
+#[test]
+fn test_giop_messages() {
+    use cdr::giop::{
+        self, CancelRequestHeader, FragmentHeader, Ior, LocateReplyHeader, LocateRequestHeader,
+        LocateStatus, Message, MessageType, ReplyHeader, ReplyStatus, RequestHeader,
+        ServiceContext, TaggedProfile, TargetAddress, Version, RESPONSE_NONE,
+        RESPONSE_SYNC_WITH_SERVER,
+    };
+
+    fn check_message(version: Version, message: Message, body: &(u8, u32)) {
+        let encoded = giop::serialize_message::<_, BigEndian>(version, &message, body).unwrap();
+        let (header, decoded, decoded_body) = giop::deserialize_message(&encoded).unwrap();
+        assert_eq!(header.version, version);
+        assert_eq!(header.message_type, message.message_type());
+        assert_eq!(decoded, message);
+        assert_eq!(decoded_body.deserialize::<(u8, u32)>().unwrap(), *body);
+
+        let encoded = giop::serialize_message::<_, LittleEndian>(version, &message, body).unwrap();
+        let (_, decoded, decoded_body) = giop::deserialize_message(&encoded).unwrap();
+        assert_eq!(decoded, message);
+        assert_eq!(decoded_body.deserialize::<(u8, u32)>().unwrap(), *body);
+    }
+
+    let service_context = vec![ServiceContext {
+        context_id: 1,
+        context_data: vec![1, 2, 3],
+    }];
+    let profile = TaggedProfile {
+        tag: 0,
+        profile_data: vec![4, 5],
+    };
+    let ior = Ior {
+        type_id: "IDL:Test:1.0".to_string(),
+        profiles: vec![profile.clone()],
+    };
+    let key = TargetAddress::KeyAddr(b"key".to_vec());
+    let body = (1, 2);
+
+    for &version in &[Version::V1_0, Version::V1_1, Version::V1_2] {
+        let request = RequestHeader {
+            request_id: 1,
+            response_flags: RESPONSE_NONE,
+            target: key.clone(),
+            operation: "op".to_string(),
+            service_context: service_context.clone(),
+            requesting_principal: if version < Version::V1_2 {
+                vec![6]
+            } else {
+                Vec::new()
+            },
+        };
+        check_message(version, Message::Request(request), &body);
+        let reply = ReplyHeader {
+            request_id: 1,
+            reply_status: ReplyStatus::UserException,
+            service_context: service_context.clone(),
+        };
+        check_message(version, Message::Reply(reply), &body);
+        check_message(
+            version,
+            Message::CancelRequest(CancelRequestHeader { request_id: 2 }),
+            &body,
+        );
+        let locate_request = LocateRequestHeader {
+            request_id: 3,
+            target: key.clone(),
+        };
+        check_message(version, Message::LocateRequest(locate_request), &body);
+        let locate_reply = LocateReplyHeader {
+            request_id: 3,
+            locate_status: LocateStatus::ObjectForward,
+        };
+        check_message(version, Message::LocateReply(locate_reply), &body);
+        check_message(version, Message::CloseConnection, &body);
+        check_message(version, Message::MessageError, &body);
+    }
+
+    check_message(Version::V1_1, Message::Fragment(None), &body);
+    check_message(
+        Version::V1_2,
+        Message::Fragment(Some(FragmentHeader { request_id: 4 })),
+        &body,
+    );
+    for target in [
+        TargetAddress::ProfileAddr(profile),
+        TargetAddress::ReferenceAddr {
+            selected_profile_index: 0,
+            ior: ior.clone(),
+        },
+    ] {
+        let request = RequestHeader {
+            request_id: 1,
+            response_flags: RESPONSE_SYNC_WITH_SERVER,
+            target: target.clone(),
+            operation: "op".to_string(),
+            service_context: Vec::new(),
+            requesting_principal: Vec::new(),
+        };
+        check_message(Version::V1_2, Message::Request(request.clone()), &body);
+        assert!(matches!(
+            giop::serialize_message::<_, BigEndian>(Version::V1_1, &Message::Request(request), &()),
+            Err(Error::InvalidMessage)
+        ));
+    }
+
+    // Statuses added in GIOP 1.2 cannot be written before.
+    let reply = Message::Reply(ReplyHeader {
+        request_id: 1,
+        reply_status: ReplyStatus::LocationForwardPerm,
+        service_context: Vec::new(),
+    });
+    check_message(Version::V1_2, reply.clone(), &body);
+    assert!(matches!(
+        giop::serialize_message::<_, BigEndian>(Version::V1_1, &reply, &ior),
+        Err(Error::InvalidMessage)
+    ));
+    assert!(matches!(
+        giop::serialize_message::<_, BigEndian>(Version::V1_0, &Message::Fragment(None), &()),
+        Err(Error::InvalidMessage)
+    ));
+
+    // A fragment carries the rest of the encoded body as is.
+    let encoded = giop::serialize_message_bytes::<BigEndian>(
+        Version::V1_2,
+        &Message::Fragment(Some(FragmentHeader { request_id: 4 })),
+        &[1, 2, 3],
+        true,
+    )
+    .unwrap();
+    let (header, _, body) = giop::deserialize_message(&encoded).unwrap();
+    assert!(header.more_fragments);
+    assert_eq!(header.message_type, MessageType::Fragment);
+    assert_eq!(body.as_bytes(), [1, 2, 3]);
+}
+
+#[test]
+fn test_ascii_only_string() {
+    let mut encoded = Vec::new();
+    let mut serializer =
+        cdr::ser::Serializer::<_, BigEndian>::new(&mut encoded).with_ascii_only(true);
+    assert!(serde::Serialize::serialize("abc", &mut serializer).is_ok());
+    assert!(matches!(
+        serde::Serialize::serialize("Zürich", &mut serializer),
+        Err(Error::InvalidString(_))
+    ));
+
+    let encoded = cdr::ser::serialize_data::<_, _, BigEndian>("Zürich", Infinite).unwrap();
+    let mut deserializer =
+        cdr::Deserializer::<_, _, BigEndian>::new(&encoded[..], Infinite).with_ascii_only(true);
+    assert!(matches!(
+        <String as serde::Deserialize>::deserialize(&mut deserializer),
+        Err(Error::InvalidString(s)) if s == "Zürich"
+    ));
 }
 
 #[test]
@@ -771,43 +1933,962 @@ fn test_union() {
 }
 
 #[test]
-fn test_unsupported() {
-    use std::collections::{BTreeMap, HashMap};
+fn test_xcdr2_alignment() {
+    check_encapsulation::<_, Cdr2Be, Cdr2Le>(1u64, 8);
+    check_encapsulation::<_, Cdr2Be, Cdr2Le>(('a', 1u64), 1 + 3 + 8);
+    check_encapsulation::<_, Cdr2Be, Cdr2Le>((1u16, 1i64), 2 + 2 + 8);
+    check_encapsulation::<_, Cdr2Be, Cdr2Le>((1u32, 1f64), 4 + 8);
+    check_encapsulation::<_, Cdr2Be, Cdr2Le>((true, 1f64), 1 + 3 + 8);
+    check_encapsulation::<_, Cdr2Be, Cdr2Le>((1f32, 1u16), 4 + 2);
+    check_encapsulation::<_, Cdr2Be, Cdr2Le>(vec![0u64, 1, 2], 4 + 8 * 3);
+    check_encapsulation::<_, Cdr2Be, Cdr2Le>(vec![vec![1i64, 3, 5], vec![-1, -3, -5]], 4 + 28 * 2);
+    check_encapsulation::<_, Cdr2Be, Cdr2Le>(("a".to_string(), 1u64), 6 + 2 + 8);
+}
 
-    fn check_error_kind<T: Debug>(res: Result<T>) {
-        match res {
-            Err(e) => match e {
-                Error::TypeNotSupported => (),
-                e => panic!("unexpected error kind: {}", e),
-            },
-            _ => panic!("should be error"),
+#[test]
+fn test_xcdr2_dheader() {
+    let names = Delimited(vec!["a".to_string()]);
+    let encoded = cdr::serialize::<_, _, Cdr2Le>(&names, Infinite).unwrap();
+    assert_eq!(
+        encoded,
+        vec![
+            0x00, 0x07, 0x00, 0x02, // two bytes of padding at the end
+            0x0a, 0x00, 0x00, 0x00, // DHEADER
+            0x01, 0x00, 0x00, 0x00, // number of elements
+            0x02, 0x00, 0x00, 0x00, //
+            b'a', 0x00, 0x00, 0x00,
+        ]
+    );
+    assert_eq!(
+        cdr::deserialize::<Delimited<Vec<String>>>(&encoded).unwrap(),
+        names
+    );
+    check_encapsulation::<_, Cdr2Be, Cdr2Le>(names.clone(), 4 + 4 + 6);
+    // XCDR1 has no DHEADER.
+    check_encapsulation::<_, CdrBe, CdrLe>(names, 4 + 6);
+
+    // Without `Delimited`, the elements follow the number of elements at once.
+    let encoded = cdr::serialize::<_, _, Cdr2Le>(&vec!["a".to_string()], Infinite).unwrap();
+    assert_eq!(
+        encoded,
+        vec![
+            0x00, 0x07, 0x00, 0x02, //
+            0x01, 0x00, 0x00, 0x00, //
+            0x02, 0x00, 0x00, 0x00, //
+            b'a', 0x00, 0x00, 0x00,
+        ]
+    );
+
+    // Arrays have no number of elements.
+    let encoded = cdr::serialize::<_, _, Cdr2Le>(&Delimited(["a", "b"]), Infinite).unwrap();
+    assert_eq!(
+        encoded,
+        vec![
+            0x00, 0x07, 0x00, 0x02, //
+            0x0e, 0x00, 0x00, 0x00, //
+            0x02, 0x00, 0x00, 0x00, //
+            b'a', 0x00, 0x00, 0x00, //
+            0x02, 0x00, 0x00, 0x00, //
+            b'b', 0x00, 0x00, 0x00,
+        ]
+    );
+    assert_eq!(
+        cdr::deserialize::<Delimited<[String; 2]>>(&encoded).unwrap(),
+        Delimited(["a".to_string(), "b".to_string()])
+    );
+
+    // An empty collection has a DHEADER too.
+    let empty = Delimited(Vec::<String>::new());
+    let encoded = cdr::serialize::<_, _, Cdr2Le>(&empty, Infinite).unwrap();
+    assert_eq!(
+        encoded,
+        vec![
+            0x00, 0x07, 0x00, 0x00, //
+            0x04, 0x00, 0x00, 0x00, //
+            0x00, 0x00, 0x00, 0x00,
+        ]
+    );
+    check_encapsulation::<_, Cdr2Be, Cdr2Le>(empty, 4 + 4);
+
+    // So do sequences of unions and maps.
+    #[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+    enum U {
+        A(u8),
+        B(u16),
+    }
+
+    check_encapsulation::<_, Cdr2Be, Cdr2Le>(Delimited(vec![U::A(1), U::B(2)]), 4 + 4 + 5 + 3 + 6);
+    let mut map = std::collections::BTreeMap::new();
+    map.insert("a".to_string(), 1u32);
+    check_encapsulation::<_, Cdr2Be, Cdr2Le>(Delimited(map), 4 + 4 + 6 + 2 + 4);
+
+    // Whatever follows the elements up to the end of the DHEADER is skipped.
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct S {
+        v: Delimited<Vec<String>>,
+        n: u32,
+    }
+
+    let encoded = [
+        0x00, 0x07, 0x00, 0x00, //
+        0x10, 0x00, 0x00, 0x00, //
+        0x01, 0x00, 0x00, 0x00, //
+        0x02, 0x00, 0x00, 0x00, //
+        b'a', 0x00, 0x00, 0x00, //
+        0xff, 0xff, 0xff, 0xff, //
+        0x07, 0x00, 0x00, 0x00,
+    ];
+    assert_eq!(
+        cdr::deserialize::<S>(&encoded).unwrap(),
+        S {
+            v: Delimited(vec!["a".to_string()]),
+            n: 7
         }
+    );
+
+    // Only collections can be delimited.
+    assert!(matches!(
+        cdr::serialize::<_, _, Cdr2Le>(&Delimited(1u32), Infinite),
+        Err(Error::TypeNotSupported)
+    ));
+    assert!(matches!(
+        cdr::deserialize::<Delimited<u32>>(&[0x00, 0x07, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00]),
+        Err(Error::TypeNotSupported)
+    ));
+}
+
+#[test]
+fn test_xcdr2_struct() {
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct S {
+        c: char,
+        n: i32,
+        b: bool,
+        m: u64,
+        s: String,
     }
 
-    check_error_kind(cdr::ser::serialize_data::<_, _, BigEndian>(
-        &Some(1usize),
-        Infinite,
+    check_encapsulation::<_, Cdr2Be, Cdr2Le>(
+        S {
+            c: 'x',
+            n: -7,
+            b: true,
+            m: 17,
+            s: "hello".to_string(),
+        },
+        30,
+    );
+}
+
+#[test]
+fn test_xcdr2_bytes() {
+    let encoded = cdr::serialize::<_, _, Cdr2Be>(&(1u8, 2u64), Infinite).unwrap();
+    assert_eq!(
+        encoded,
+        vec![
+            0x00, 0x06, 0x00, 0x00, //
+            0x01, 0x00, 0x00, 0x00, //
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02,
+        ]
+    );
+
+    let encoded = cdr::serialize::<_, _, Cdr2Le>(&(1u8, 2u64), Infinite).unwrap();
+    assert_eq!(
+        encoded,
+        vec![
+            0x00, 0x07, 0x00, 0x00, //
+            0x01, 0x00, 0x00, 0x00, //
+            0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        ]
+    );
+}
+
+#[test]
+fn test_delimited_struct() {
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Inner {
+        a: u8,
+        b: u64,
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Outer {
+        i: Inner,
+        c: u16,
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct AnnotatedOuter {
+        i: Appendable<Inner>,
+        c: u16,
+    }
+
+    check_encapsulation::<_, DCdr2Be, DCdr2Le>(Inner { a: 1, b: 2 }, 4 + 1 + 3 + 8);
+    // Nested structs are final unless annotated otherwise.
+    check_encapsulation::<_, DCdr2Be, DCdr2Le>(
+        Outer {
+            i: Inner { a: 1, b: 2 },
+            c: 3,
+        },
+        4 + (1 + 3 + 8) + 2,
+    );
+    check_encapsulation::<_, DCdr2Be, DCdr2Le>(
+        Final(AnnotatedOuter {
+            i: Appendable(Inner { a: 1, b: 2 }),
+            c: 3,
+        }),
+        (4 + 1 + 3 + 8) + 2,
+    );
+    check_encapsulation::<_, Cdr2Be, Cdr2Le>(
+        AnnotatedOuter {
+            i: Appendable(Inner { a: 1, b: 2 }),
+            c: 3,
+        },
+        (4 + 1 + 3 + 8) + 2,
+    );
+    // Only structs can be annotated.
+    assert!(matches!(
+        cdr::serialize::<_, _, Cdr2Le>(&Appendable(1u32), Infinite),
+        Err(Error::TypeNotSupported)
     ));
-    check_error_kind(cdr::ser::serialize_data::<_, _, BigEndian>(
-        &None::<usize>,
-        Infinite,
+    assert!(matches!(
+        cdr::serialize::<_, _, Cdr2Le>(&Mutable(vec![Inner { a: 1, b: 2 }]), Infinite),
+        Err(Error::TypeNotSupported)
     ));
-    check_error_kind(cdr::ser::serialize_data::<_, _, BigEndian>(
-        &HashMap::<usize, usize>::new(),
+    assert!(matches!(
+        cdr::deserialize::<Final<u32>>(&[0x00, 0x07, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00]),
+        Err(Error::TypeNotSupported)
+    ));
+    check_encapsulation::<_, DCdr2Be, DCdr2Le>(vec![(1u8, 2u16)], 4 + 1 + 1 + 2);
+}
+
+#[test]
+fn test_delimited_bytes() {
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct S {
+        a: u8,
+        b: u32,
+    }
+
+    let encoded = cdr::serialize::<_, _, DCdr2Be>(&S { a: 1, b: 2 }, Infinite).unwrap();
+    assert_eq!(
+        encoded,
+        vec![
+            0x00, 0x08, 0x00, 0x00, //
+            0x00, 0x00, 0x00, 0x08, //
+            0x01, 0x00, 0x00, 0x00, //
+            0x00, 0x00, 0x00, 0x02,
+        ]
+    );
+
+    let encoded = cdr::serialize::<_, _, DCdr2Le>(&S { a: 1, b: 2 }, Infinite).unwrap();
+    assert_eq!(
+        encoded,
+        vec![
+            0x00, 0x09, 0x00, 0x00, //
+            0x08, 0x00, 0x00, 0x00, //
+            0x01, 0x00, 0x00, 0x00, //
+            0x02, 0x00, 0x00, 0x00,
+        ]
+    );
+}
+
+#[test]
+fn test_delimited_evolution() {
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct V1 {
+        a: u32,
+        b: u16,
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct V2 {
+        a: u32,
+        b: u16,
+        #[serde(default)]
+        c: String,
+        #[serde(default)]
+        d: u64,
+    }
+
+    let v2 = (
+        V2 {
+            a: 1,
+            b: 2,
+            c: "new".to_string(),
+            d: 3,
+        },
+        7u8,
+    );
+    let encoded = cdr::serialize::<_, _, DCdr2Le>(&v2, Infinite).unwrap();
+    let decoded: (V1, u8) = cdr::deserialize(&encoded).unwrap();
+    assert_eq!((V1 { a: 1, b: 2 }, 7), decoded);
+
+    let v1 = (V1 { a: 1, b: 2 }, 7u8);
+    let encoded = cdr::serialize::<_, _, DCdr2Be>(&v1, Infinite).unwrap();
+    let decoded: (V2, u8) = cdr::deserialize(&encoded).unwrap();
+    assert_eq!(
+        (
+            V2 {
+                a: 1,
+                b: 2,
+                c: String::new(),
+                d: 0,
+            },
+            7
+        ),
+        decoded
+    );
+
+    let mut encoded = cdr::serialize::<_, _, DCdr2Be>(&v1, Infinite).unwrap();
+    encoded[7] = 2; // shrinks the DHEADER below the size of the members
+    assert!(matches!(
+        cdr::deserialize::<(V1, u8)>(&encoded).unwrap_err(),
+        Error::LengthExceeded
+    ));
+}
+
+#[test]
+fn test_parameter_list_struct() {
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct S {
+        c: char,
+        n: i32,
+        b: bool,
+        m: u64,
+        s: String,
+    }
+
+    check_encapsulation::<_, PlCdrBe, PlCdrLe>(
+        S {
+            c: 'x',
+            n: -7,
+            b: true,
+            m: 17,
+            s: "hello".to_string(),
+        },
+        60,
+    );
+    check_encapsulation::<_, PlCdrBe, PlCdrLe>(vec![(1u8, 2u16)], 4 + 1 + 1 + 2);
+}
+
+#[test]
+fn test_parameter_list_bytes() {
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct S {
+        a: u8,
+        b: u32,
+    }
+
+    let encoded = cdr::serialize::<_, _, PlCdrBe>(&S { a: 1, b: 2 }, Infinite).unwrap();
+    assert_eq!(
+        encoded,
+        vec![
+            0x00, 0x02, 0x00, 0x00, //
+            0x00, 0x00, 0x00, 0x04, //
+            0x01, 0x00, 0x00, 0x00, //
+            0x00, 0x01, 0x00, 0x04, //
+            0x00, 0x00, 0x00, 0x02, //
+            0x3f, 0x02, 0x00, 0x00,
+        ]
+    );
+
+    let encoded = cdr::serialize::<_, _, PlCdrLe>(&S { a: 1, b: 2 }, Infinite).unwrap();
+    assert_eq!(
+        encoded,
+        vec![
+            0x00, 0x03, 0x00, 0x00, //
+            0x00, 0x00, 0x04, 0x00, //
+            0x01, 0x00, 0x00, 0x00, //
+            0x01, 0x00, 0x04, 0x00, //
+            0x02, 0x00, 0x00, 0x00, //
+            0x02, 0x3f, 0x00, 0x00,
+        ]
+    );
+}
+
+#[test]
+fn test_parameter_list_decoding() {
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct S {
+        a: u16,
+        b: u32,
+        #[serde(rename = "c@0x4000")]
+        c: u8,
+    }
+
+    let encoded = vec![
+        0x00, 0x03, 0x00, 0x00, //
+        0x07, 0x00, 0x04, 0x00, // unknown member
+        0xff, 0xff, 0xff, 0xff, //
+        0x01, 0x00, 0x04, 0x00, // b
+        0x02, 0x00, 0x00, 0x00, //
+        0x01, 0x3f, 0x08, 0x00, // c in an extended header
+        0x00, 0x40, 0x00, 0x00, //
+        0x04, 0x00, 0x00, 0x00, //
+        0x03, 0x00, 0x00, 0x00, //
+        0x00, 0x00, 0x02, 0x00, // a
+        0x01, 0x00, 0x00, 0x00, //
+        0x02, 0x3f, 0x00, 0x00,
+    ];
+    assert_eq!(
+        S { a: 1, b: 2, c: 3 },
+        cdr::deserialize::<S>(&encoded).unwrap()
+    );
+
+    let encoded = cdr::serialize::<_, _, PlCdrLe>(&S { a: 1, b: 2, c: 3 }, Infinite).unwrap();
+    assert_eq!(
+        &encoded[20..36],
+        &[
+            0x01, 0x3f, 0x08, 0x00, //
+            0x00, 0x40, 0x00, 0x00, //
+            0x04, 0x00, 0x00, 0x00, //
+            0x03, 0x00, 0x00, 0x00,
+        ]
+    );
+    check_encapsulation::<_, PlCdrBe, PlCdrLe>(S { a: 1, b: 2, c: 3 }, 36);
+}
+
+#[test]
+fn test_parameter_list_nested() {
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Locator {
+        kind: i32,
+        port: u32,
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Data {
+        #[serde(rename = "loc@0x2f")]
+        loc: Locator,
+    }
+
+    // Only the outermost struct is a parameter list, as in RTPS discovery
+    // data.
+    let encoded = vec![
+        0x00, 0x03, 0x00, 0x00, //
+        0x2f, 0x00, 0x08, 0x00, // loc
+        0x01, 0x00, 0x00, 0x00, //
+        0xe8, 0x1c, 0x00, 0x00, //
+        0x02, 0x3f, 0x00, 0x00, // sentinel
+    ];
+    let value = Data {
+        loc: Locator {
+            kind: 1,
+            port: 7400,
+        },
+    };
+    assert_eq!(cdr::deserialize::<Data>(&encoded).unwrap(), value);
+    assert_eq!(
+        cdr::serialize::<_, _, PlCdrLe>(&value, Infinite).unwrap(),
+        encoded
+    );
+    check_encapsulation::<_, PlCdrBe, PlCdrLe>(value, 4 + 8 + 4);
+}
+
+#[test]
+fn test_parameter_list_evolution() {
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct V1 {
+        a: u32,
+        b: String,
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct V2 {
+        a: u32,
+        #[serde(default, skip_serializing_if = "String::is_empty")]
+        b: String,
+        #[serde(default)]
+        c: Vec<u64>,
+    }
+
+    let encoded = cdr::serialize::<_, _, PlCdrBe>(
+        &(
+            V2 {
+                a: 1,
+                b: String::new(),
+                c: vec![2, 3],
+            },
+            4u8,
+        ),
+        Infinite,
+    )
+    .unwrap();
+    assert!(cdr::deserialize::<(V1, u8)>(&encoded).is_err());
+
+    let encoded = cdr::serialize::<_, _, PlCdrLe>(
+        &(
+            V1 {
+                a: 1,
+                b: "hi".to_string(),
+            },
+            4u8,
+        ),
         Infinite,
+    )
+    .unwrap();
+    assert_eq!(
+        (
+            V2 {
+                a: 1,
+                b: "hi".to_string(),
+                c: vec![],
+            },
+            4
+        ),
+        cdr::deserialize::<(V2, u8)>(&encoded).unwrap()
+    );
+}
+
+#[test]
+fn test_parameter_list_must_understand() {
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct S {
+        a: u32,
+    }
+
+    let encoded = vec![
+        0x00, 0x02, 0x00, 0x00, //
+        0x40, 0x07, 0x00, 0x04, // unknown must-understand member
+        0x00, 0x00, 0x00, 0x01, //
+        0x00, 0x00, 0x00, 0x04, //
+        0x00, 0x00, 0x00, 0x02, //
+        0x3f, 0x02, 0x00, 0x00,
+    ];
+    assert!(matches!(
+        cdr::deserialize::<S>(&encoded).unwrap_err(),
+        Error::UnknownMustUnderstand(7)
     ));
-    check_error_kind(cdr::ser::serialize_data::<_, _, BigEndian>(
-        &BTreeMap::<usize, usize>::new(),
+
+    let encoded = vec![
+        0x00, 0x0a, 0x00, 0x00, //
+        0x00, 0x00, 0x00, 0x10, //
+        0xa0, 0x00, 0x00, 0x07, // unknown must-understand member
+        0x00, 0x00, 0x00, 0x01, //
+        0x20, 0x00, 0x00, 0x00, //
+        0x00, 0x00, 0x00, 0x02,
+    ];
+    assert!(matches!(
+        cdr::deserialize::<S>(&encoded).unwrap_err(),
+        Error::UnknownMustUnderstand(7)
+    ));
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct K {
+        id: Key<u32>,
+        b: u16,
+        #[serde(rename = "e@0x4000")]
+        e: Key<u8>,
+    }
+
+    let value = K {
+        id: Key(1),
+        b: 2,
+        e: Key(3),
+    };
+    let encoded = cdr::serialize::<_, _, PlCdrBe>(&value, Infinite).unwrap();
+    assert_eq!(
+        encoded,
+        vec![
+            0x00, 0x02, 0x00, 0x00, //
+            0x40, 0x00, 0x00, 0x04, // id with the must-understand flag
+            0x00, 0x00, 0x00, 0x01, //
+            0x00, 0x01, 0x00, 0x04, //
+            0x00, 0x02, 0x00, 0x00, //
+            0x3f, 0x01, 0x00, 0x08, // e with the must-understand flag
+            0x40, 0x00, 0x40, 0x00, //
+            0x00, 0x00, 0x00, 0x04, //
+            0x03, 0x00, 0x00, 0x00, //
+            0x3f, 0x02, 0x00, 0x00,
+        ]
+    );
+    assert_eq!(cdr::deserialize::<K>(&encoded).unwrap(), value);
+
+    let encoded = cdr::serialize::<_, _, PlCdr2Be>(&value, Infinite).unwrap();
+    assert_eq!(
+        encoded,
+        vec![
+            0x00, 0x0a, 0x00, 0x03, //
+            0x00, 0x00, 0x00, 0x15, //
+            0xa0, 0x00, 0x00, 0x00, // id with the must-understand flag
+            0x00, 0x00, 0x00, 0x01, //
+            0x10, 0x00, 0x00, 0x01, //
+            0x00, 0x02, 0x00, 0x00, //
+            0x80, 0x00, 0x40, 0x00, // e with the must-understand flag
+            0x03, 0x00, 0x00, 0x00,
+        ]
+    );
+    assert_eq!(cdr::deserialize::<K>(&encoded).unwrap(), value);
+
+    // Keys of final structs have no header to flag.
+    check_encapsulation::<_, CdrBe, CdrLe>(value, 4 + 2 + 1);
+}
+
+#[test]
+fn test_xcdr2_parameter_list_struct() {
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Inner {
+        x: u16,
+        y: u16,
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct S {
+        c: char,
+        n: i32,
+        b: bool,
+        m: u64,
+        s: String,
+        v: Vec<u16>,
+        i: Inner,
+    }
+
+    check_encapsulation::<_, PlCdr2Be, PlCdr2Le>(
+        S {
+            c: 'x',
+            n: -7,
+            b: true,
+            m: 17,
+            s: "hello".to_string(),
+            v: vec![1, 2, 3],
+            i: Inner { x: 4, y: 5 },
+        },
+        // The nested struct is final, so its 4 bytes need no NEXTINT.
+        84,
+    );
+    check_encapsulation::<_, PlCdr2Be, PlCdr2Le>(vec![(1u8, 2u16)], 4 + 1 + 1 + 2);
+}
+
+#[test]
+fn test_xcdr2_parameter_list_bytes() {
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct S {
+        a: u8,
+        b: u32,
+        s: String,
+    }
+
+    let value = S {
+        a: 1,
+        b: 2,
+        s: "ab".to_string(),
+    };
+    let encoded = cdr::serialize::<_, _, PlCdr2Be>(&value, Infinite).unwrap();
+    assert_eq!(
+        encoded,
+        vec![
+            0x00, 0x0a, 0x00, 0x01, // one byte of padding at the end
+            0x00, 0x00, 0x00, 0x1b, //
+            0x00, 0x00, 0x00, 0x00, // a with a length code of 0
+            0x01, 0x00, 0x00, 0x00, //
+            0x20, 0x00, 0x00, 0x01, // b with a length code of 2
+            0x00, 0x00, 0x00, 0x02, //
+            0x50, 0x00, 0x00, 0x02, // s with a length code of 5
+            0x00, 0x00, 0x00, 0x03, //
+            0x61, 0x62, 0x00, 0x00,
+        ]
+    );
+
+    let encoded = cdr::serialize::<_, _, PlCdr2Le>(&value, Infinite).unwrap();
+    assert_eq!(
+        encoded,
+        vec![
+            0x00, 0x0b, 0x00, 0x01, //
+            0x1b, 0x00, 0x00, 0x00, //
+            0x00, 0x00, 0x00, 0x00, //
+            0x01, 0x00, 0x00, 0x00, //
+            0x01, 0x00, 0x00, 0x20, //
+            0x02, 0x00, 0x00, 0x00, //
+            0x02, 0x00, 0x00, 0x50, //
+            0x03, 0x00, 0x00, 0x00, //
+            0x61, 0x62, 0x00, 0x00,
+        ]
+    );
+}
+
+#[test]
+fn test_xcdr2_parameter_list_decoding() {
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct S {
+        a: Vec<u32>,
+        b: Vec<u64>,
+        c: u16,
+    }
+
+    let encoded = vec![
+        0x00, 0x0b, 0x00, 0x00, //
+        0x32, 0x00, 0x00, 0x00, //
+        0x09, 0x00, 0x00, 0x20, // unknown member
+        0xff, 0xff, 0xff, 0xff, //
+        0x01, 0x00, 0x00, 0x70, // b with a length code of 7
+        0x01, 0x00, 0x00, 0x00, //
+        0x05, 0x00, 0x00, 0x00, //
+        0x00, 0x00, 0x00, 0x00, //
+        0x00, 0x00, 0x00, 0x60, // a with a length code of 6
+        0x02, 0x00, 0x00, 0x00, //
+        0x03, 0x00, 0x00, 0x00, //
+        0x04, 0x00, 0x00, 0x00, //
+        0x02, 0x00, 0x00, 0x40, // c with a NEXTINT
+        0x02, 0x00, 0x00, 0x00, //
+        0x06, 0x00,
+    ];
+    let value = S {
+        a: vec![3, 4],
+        b: vec![5],
+        c: 6,
+    };
+    assert_eq!(value, cdr::deserialize::<S>(&encoded).unwrap());
+
+    let encoded = cdr::serialize::<_, _, PlCdr2Le>(&value, Infinite).unwrap();
+    assert_eq!(&encoded[8..12], &[0x00, 0x00, 0x00, 0x60]);
+    assert_eq!(&encoded[24..28], &[0x01, 0x00, 0x00, 0x70]);
+    assert_eq!(&encoded[40..44], &[0x02, 0x00, 0x00, 0x10]);
+}
+
+#[test]
+fn test_xcdr2_parameter_list_evolution() {
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct V1 {
+        a: u32,
+        #[serde(rename = "b@5")]
+        b: String,
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct V2 {
+        #[serde(rename = "c@2")]
+        c: Vec<u8>,
+        #[serde(rename = "b@5")]
+        b: String,
+        #[serde(rename = "a@0")]
+        a: u32,
+    }
+
+    let encoded = cdr::serialize::<_, _, PlCdr2Be>(
+        &(
+            V2 {
+                c: vec![1, 2, 3],
+                b: "hi".to_string(),
+                a: 7,
+            },
+            4u8,
+        ),
         Infinite,
+    )
+    .unwrap();
+    assert_eq!(
+        (
+            V1 {
+                a: 7,
+                b: "hi".to_string(),
+            },
+            4
+        ),
+        cdr::deserialize::<(V1, u8)>(&encoded).unwrap()
+    );
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+struct Optionals {
+    a: u8,
+    b: Option<u32>,
+    c: Option<u16>,
+    d: u8,
+}
+
+const OPTIONALS: Optionals = Optionals {
+    a: 1,
+    b: Some(7),
+    c: None,
+    d: 5,
+};
+
+#[test]
+fn test_optional() {
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Unit {
+        a: Option<()>,
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Pair {
+        a: (u8, Option<u8>),
+    }
+
+    check(OPTIONALS, Some(17));
+    check(Unit { a: None }, Some(4));
+    check_encapsulation::<_, Cdr2Be, Cdr2Le>(Unit { a: Some(()) }, 1);
+    // A present member of zero length is indistinguishable from an absent one
+    // in XCDR1.
+    assert!(matches!(
+        cdr::serialize::<_, _, CdrBe>(&Unit { a: Some(()) }, Infinite),
+        Err(Error::TypeNotSupported)
+    ));
+    assert!(matches!(
+        cdr::calc_serialized_size_bounded(&Unit { a: Some(()) }, u64::MAX),
+        Err(Error::TypeNotSupported)
     ));
 
-    check_error_kind(cdr::de::deserialize_data::<Option<usize>, BigEndian>(
-        Vec::new().as_slice(),
+    // Options outside struct members are preceded by a presence flag.
+    check(Some(1u32), Some(8));
+    check(None::<u32>, Some(1));
+    check(vec![Some(1u8), None], Some(4 + 2 + 1));
+    check(Pair { a: (1, None) }, Some(2));
+    check(Pair { a: (1, Some(2)) }, Some(3));
+    check_encapsulation::<_, Cdr2Be, Cdr2Le>(Pair { a: (1, Some(2)) }, 3);
+    assert_eq!(
+        cdr::serialize::<_, _, CdrBe>(&(Some(7u16), None::<u8>), Infinite).unwrap(),
+        vec![0, 0, 0, 0, 1, 0, 0, 7, 0]
+    );
+    assert!(matches!(
+        cdr::deserialize::<Option<u32>>(&[0, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0]),
+        Err(Error::InvalidBoolEncoding(2))
     ));
-    check_error_kind(
-        cdr::de::deserialize_data::<HashMap<usize, usize>, BigEndian>(Vec::new().as_slice()),
+
+    let encoded = cdr::serialize::<_, _, CdrBe>(&OPTIONALS, Infinite).unwrap();
+    assert_eq!(
+        encoded,
+        vec![
+            0x00, 0x00, 0x00, 0x00, //
+            0x01, 0x00, 0x00, 0x00, //
+            0x00, 0x01, 0x00, 0x04, // b in a parameter header
+            0x00, 0x00, 0x00, 0x07, //
+            0x00, 0x02, 0x00, 0x00, // c absent
+            0x05,
+        ]
     );
+}
+
+#[test]
+fn test_xcdr2_optional() {
+    check_encapsulation::<_, Cdr2Be, Cdr2Le>(OPTIONALS, 10);
+    check_encapsulation::<_, DCdr2Be, DCdr2Le>(OPTIONALS, 14);
+
+    let encoded = cdr::serialize::<_, _, Cdr2Be>(&OPTIONALS, Infinite).unwrap();
+    assert_eq!(
+        encoded,
+        vec![
+            0x00, 0x06, 0x00, 0x02, //
+            0x01, 0x01, 0x00, 0x00, // b present
+            0x00, 0x00, 0x00, 0x07, //
+            0x00, 0x05, 0x00, 0x00, // c absent
+        ]
+    );
+}
+
+#[test]
+fn test_parameter_list_optional() {
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct V1 {
+        a: u8,
+        b: Option<u32>,
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct V2 {
+        a: u8,
+        b: Option<u32>,
+        c: Option<String>,
+    }
+
+    check_encapsulation::<_, PlCdrBe, PlCdrLe>(OPTIONALS, 28);
+    check_encapsulation::<_, PlCdr2Be, PlCdr2Le>(OPTIONALS, 25);
+    check_encapsulation::<_, PlCdr2Be, PlCdr2Le>(
+        V2 {
+            a: 1,
+            b: None,
+            c: Some("hi".to_string()),
+        },
+        4 + 8 + 4 + 7,
+    );
+
+    let encoded = cdr::serialize::<_, _, PlCdrBe>(&OPTIONALS, Infinite).unwrap();
+    assert_eq!(
+        encoded,
+        vec![
+            0x00, 0x02, 0x00, 0x00, //
+            0x00, 0x00, 0x00, 0x04, //
+            0x01, 0x00, 0x00, 0x00, //
+            0x00, 0x01, 0x00, 0x04, //
+            0x00, 0x00, 0x00, 0x07, //
+            0x00, 0x03, 0x00, 0x04, // d follows b as c is absent
+            0x05, 0x00, 0x00, 0x00, //
+            0x3f, 0x02, 0x00, 0x00,
+        ]
+    );
+
+    let encoded = cdr::serialize::<_, _, PlCdr2Le>(&V1 { a: 1, b: Some(2) }, Infinite).unwrap();
+    assert_eq!(
+        V2 {
+            a: 1,
+            b: Some(2),
+            c: None,
+        },
+        cdr::deserialize::<V2>(&encoded).unwrap()
+    );
+}
+
+#[test]
+fn test_map() {
+    use std::collections::{BTreeMap, HashMap};
+
+    check(BTreeMap::<u8, u32>::new(), Some(4));
+    check(
+        vec![(1u16, 2u64), (3, 4)]
+            .into_iter()
+            .collect::<BTreeMap<_, _>>(),
+        Some(4 + 2 + 2 + 8 + 2 + 6 + 8),
+    );
+    check(
+        vec![(1u32, "one".to_string())]
+            .into_iter()
+            .collect::<HashMap<_, _>>(),
+        Some(4 + 4 + 4 + 4),
+    );
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct S {
+        a: u8,
+        m: BTreeMap<String, i16>,
+    }
+
+    let value = S {
+        a: 1,
+        m: vec![("x".to_string(), -1), ("yz".to_string(), 2)]
+            .into_iter()
+            .collect(),
+    };
+    let encoded = cdr::serialize::<_, _, CdrBe>(&value, Infinite).unwrap();
+    assert_eq!(
+        encoded,
+        vec![
+            0x00, 0x00, 0x00, 0x00, //
+            0x01, 0x00, 0x00, 0x00, //
+            0x00, 0x00, 0x00, 0x02, // number of pairs
+            0x00, 0x00, 0x00, 0x02, //
+            b'x', 0x00, 0xff, 0xff, //
+            0x00, 0x00, 0x00, 0x03, //
+            b'y', b'z', 0x00, 0x00, //
+            0x00, 0x02,
+        ]
+    );
+    check_encapsulation::<_, Cdr2Be, Cdr2Le>(value, 26);
+}
+
+#[test]
+fn test_unsupported() {
+    fn check_error_kind<T: Debug>(res: Result<T>) {
+        match res {
+            Err(e) => match e {
+                Error::TypeNotSupported => (),
+                e => panic!("unexpected error kind: {}", e),
+            },
+            _ => panic!("should be error"),
+        }
+    }
+
     check_error_kind(
-        cdr::de::deserialize_data::<BTreeMap<usize, usize>, BigEndian>(Vec::new().as_slice()),
+        cdr::de::deserialize_data::<serde::de::IgnoredAny, BigEndian>(Vec::new().as_slice()),
     );
 }